@@ -0,0 +1,18 @@
+use std::process::Command;
+
+// Embeds the target triple and, when available, the git commit for `dl version`.
+fn main() {
+    println!("cargo:rustc-env=DL_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+
+    println!("cargo:rerun-if-env-changed=DL_GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    // Packagers building from a tarball can pass the commit in DL_GIT_COMMIT.
+    let commit = std::env::var("DL_GIT_COMMIT").ok().or_else(|| {
+        let output = Command::new("git").args(["rev-parse", "HEAD"]).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    });
+    if let Some(commit) = commit.filter(|c| !c.is_empty()) {
+        println!("cargo:rustc-env=DL_GIT_COMMIT={}", commit);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::checksum::{is_sha256_hex, sha256_file_async, ChecksumMismatchError};
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+
+/// Location of an object in the store: `<dir>/<first two hex chars>/<remaining hex chars>`.
+pub fn store_path(cas_dir: &Path, sha256: &str) -> PathBuf {
+    let sha256 = sha256.to_lowercase();
+    let (prefix, rest) = sha256.split_at(2);
+    cas_dir.join(prefix).join(rest)
+}
+
+/// Links `dest` to an already-stored object with the given hash.
+/// Returns false if the store has no such object.
+pub async fn link_if_stored(cas_dir: &Path, sha256: &str, dest: &Path) -> Result<bool> {
+    if !is_sha256_hex(sha256) {
+        return Ok(false);
+    }
+    let object = store_path(cas_dir, sha256);
+    if !object.is_file() {
+        return Ok(false);
+    }
+    debug!("Store already has {}, linking {}", sha256, dest.display());
+    replace_with_link(&object, dest).await?;
+    Ok(true)
+}
+
+/// Moves a freshly downloaded file into the store (unless an identical object is already there)
+/// and replaces it with a link to the stored object. Returns the file's hash.
+pub async fn ingest(cas_dir: &Path, dest: &Path, expected_sha256: Option<&str>) -> Result<String> {
+    let actual = sha256_file_async(dest).await?;
+    if let Some(expected) = expected_sha256 {
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(ChecksumMismatchError {
+                path: dest.to_path_buf(),
+                expected: expected.to_string(),
+                actual,
+            }
+            .into());
+        }
+    }
+
+    let object = store_path(cas_dir, &actual);
+    if !object.is_file() {
+        if let Some(parent) = object.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create store directory: {}", parent.display()))?;
+        }
+        if tokio::fs::rename(dest, &object).await.is_err() {
+            // The store may live on another filesystem, where rename is not possible.
+            tokio::fs::copy(dest, &object)
+                .await
+                .with_context(|| format!("Failed to copy {} into the store", dest.display()))?;
+        }
+        info!("Stored {} as {}", dest.display(), object.display());
+    }
+    replace_with_link(&object, dest).await?;
+    Ok(actual)
+}
+
+/// Replaces `dest` with a hardlink to `object`, falling back to a symlink and then a copy.
+pub async fn replace_with_link(object: &Path, dest: &Path) -> Result<()> {
+    if tokio::fs::symlink_metadata(dest).await.is_ok() {
+        tokio::fs::remove_file(dest)
+            .await
+            .with_context(|| format!("Failed to replace {}", dest.display()))?;
+    }
+    if tokio::fs::hard_link(object, dest).await.is_ok() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        let absolute = std::path::absolute(object)?;
+        if tokio::fs::symlink(&absolute, dest).await.is_ok() {
+            return Ok(());
+        }
+    }
+
+    tokio::fs::copy(object, dest)
+        .await
+        .with_context(|| format!("Failed to link or copy {} to {}", object.display(), dest.display()))?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Read buffer used while hashing files from disk.
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Computes the lowercase hex SHA-256 digest of a file.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {} for hashing", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read {} while hashing", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// A file's SHA-256 differs from the digest its source declared.
+#[derive(Debug)]
+pub struct ChecksumMismatchError {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for ChecksumMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Checksum mismatch for {}: expected {}, got {}",
+            self.path.display(),
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatchError {}
+
+/// Hashes a file and fails with `ChecksumMismatchError` unless it matches `expected`.
+pub async fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = sha256_file_async(path).await?;
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(ChecksumMismatchError {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Hashes a file on a blocking thread so large files don't stall the async runtime.
+pub async fn sha256_file_async(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || sha256_file(&path))
+        .await
+        .context("Hashing task panicked")?
+}
+
+/// Returns true if `value` looks like a hex-encoded SHA-256 digest.
+pub fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::collections::HashSet;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Extensions used for temporary files by interrupted downloads and self-updates.
+const TEMP_EXTENSIONS: [&str; 2] = ["part", "new"];
+
+// Temp files modified more recently than this may belong to another running instance.
+const ORPHAN_AGE_THRESHOLD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Finds temp files under `dir` that are old enough to be considered orphaned.
+/// Files whose final path (or themselves) belong to the current run are never returned,
+/// since they may be valid resumable partials.
+pub fn find_orphaned_temp_files(dir: &Path, active_paths: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut orphans = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    let now = SystemTime::now();
+
+    while let Some(current) = pending.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("Skipping unreadable directory {}: {}", current.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if !file_type.is_file() || !is_temp_file(&path) {
+                continue;
+            }
+            if active_paths.contains(&path) || active_paths.contains(&path.with_extension("")) {
+                debug!("Keeping temp file {} as it belongs to the current run", path.display());
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok());
+            match age {
+                Some(age) if age >= ORPHAN_AGE_THRESHOLD => orphans.push(path),
+                _ => debug!("Keeping recent temp file {}", path.display()),
+            }
+        }
+    }
+
+    orphans.sort();
+    orphans
+}
+
+/// Scans `dir` for orphaned temp files and removes them, prompting unless `auto_confirm` is set.
+pub fn clean_orphaned_temp_files(
+    dir: &Path,
+    active_paths: &HashSet<PathBuf>,
+    auto_confirm: bool,
+) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let orphans = find_orphaned_temp_files(dir, active_paths);
+    if orphans.is_empty() {
+        debug!("No orphaned temp files found in {}", dir.display());
+        return Ok(());
+    }
+
+    eprintln!(
+        "[INFO] Found {} orphaned temp file(s) in '{}':",
+        orphans.len(),
+        dir.display()
+    );
+    for path in &orphans {
+        eprintln!("    {}", path.display());
+    }
+
+    if !auto_confirm {
+        if !std::io::stdin().is_terminal() {
+            eprintln!("[INFO] Use --clean to remove them.");
+            return Ok(());
+        }
+        eprint!("Remove them? [y/N]: ");
+        std::io::stderr().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            eprintln!("[INFO] Leaving temp files in place.");
+            return Ok(());
+        }
+    }
+
+    for path in &orphans {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove temp file: {}", path.display()))?;
+        info!("Removed orphaned temp file {}", path.display());
+    }
+    eprintln!("[INFO] Removed {} orphaned temp file(s).", orphans.len());
+    Ok(())
+}
+
+fn is_temp_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| TEMP_EXTENSIONS.contains(&e))
+}
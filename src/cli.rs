@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -38,10 +38,159 @@ pub struct Cli {
     #[arg(short, long, default_value_t = 3)]
     pub concurrency: usize,
 
+    /// How many times to retry a failed download (with backoff) before giving up.
+    #[arg(long, default_value_t = 3)]
+    pub retries: u32,
+
+    /// Give up immediately on DNS and connection-refused errors instead of retrying them.
+    #[arg(long)]
+    pub no_retry_connect_errors: bool,
+
+    /// Buffer size for disk writes, e.g. 256KiB or 1M.
+    #[arg(long, default_value = "256KiB", value_parser = parse_chunk_size)]
+    pub chunk_size: usize,
+
+    /// Conservative mode for metered links: one download at a time, no parallel prescan,
+    /// and a confirmation prompt for runs over 1 GB. Also enabled by DL_METERED=1.
+    #[arg(long)]
+    pub metered: bool,
+
+    /// Keep downloading the remaining files after a failure (default).
+    #[arg(long, conflicts_with = "fail_fast")]
+    pub keep_going: bool,
+
+    /// Cancel remaining downloads and exit non-zero on the first failed file (after its retries).
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Treat files the server reports as gone (HTTP 404/410) as skipped rather than failed.
+    #[arg(long)]
+    pub skip_missing: bool,
+
+    /// List the files that would be downloaded, with sizes and checksum availability, then exit.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Check every file's SHA-256 and fail any file whose source declares no checksum.
+    #[arg(long)]
+    pub strict_verify: bool,
+
+    /// On a checksum mismatch, delete the file and download it again up to N times before failing it.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retry_on_checksum_mismatch: u32,
+
+    /// When the disk fills up, let running downloads try to finish but start no new ones.
+    #[arg(long)]
+    pub continue_on_disk_full: bool,
+
+    /// Fetch a sha256sum-style checksums file (e.g. SHA256SUMS) and verify each download against its entry.
+    #[arg(long, value_name = "URL")]
+    pub checksums_url: Option<String>,
+
+    /// After each file is written, read it back from disk and check its size and SHA-256 (when known).
+    #[arg(long)]
+    pub verify_readback: bool,
+
+    /// Download each large file over up to N parallel range requests.
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub split: usize,
+
+    /// Smallest part a split download may use; smaller files use fewer parts or a single stream.
+    #[arg(long, default_value = "64MiB", value_name = "SIZE", value_parser = crate::util::parse_size)]
+    pub min_split_size: u64,
+
+    /// Prescan every file's size with the server, even when the HF API already reported it.
+    #[arg(long)]
+    pub force_prescan: bool,
+
+    /// (Hugging Face only) Check files already on disk against the Hub's sizes and SHA-256s, re-download only missing or corrupt ones, then verify the whole set.
+    #[arg(long, requires = "hf", conflicts_with_all = ["dry_run", "output", "top_largest", "top_smallest"])]
+    pub repair: bool,
+
+    /// Download only the N largest files, by their prescanned sizes.
+    #[arg(long, value_name = "N", conflicts_with = "top_smallest")]
+    pub top_largest: Option<usize>,
+
+    /// Download only the N smallest files, by their prescanned sizes.
+    #[arg(long, value_name = "N")]
+    pub top_smallest: Option<usize>,
+
+    /// Start files whose name matches this glob before all others (repeatable).
+    #[arg(long, value_name = "GLOB")]
+    pub priority: Vec<String>,
+
+    /// Download files in order until their total size would exceed SIZE, e.g. 50GB; the rest are left out.
+    #[arg(long, value_name = "SIZE", value_parser = crate::util::parse_size)]
+    pub max_total_size: Option<u64>,
+
+    /// Download the files in random order, e.g. to spread load across hosts.
+    #[arg(long)]
+    pub shuffle: bool,
+
+    /// Seed for --shuffle, to make the order reproducible.
+    #[arg(long, value_name = "N", requires = "shuffle")]
+    pub seed: Option<u64>,
+
+    /// Cancel a download attempt that hasn't finished after this many seconds; it is then retried
+    /// like any other failure.
+    #[arg(long, value_name = "SECS")]
+    pub timeout_per_file: Option<u64>,
+
+    /// Re-download files whose name matches these globs (comma-separated or repeated),
+    /// even if they already exist.
+    #[arg(long, value_name = "NAMES_OR_GLOBS", value_delimiter = ',')]
+    pub force_files: Vec<String>,
+
+    /// Put this run's downloads in a subdirectory named after its start time.
+    #[arg(long, conflicts_with_all = ["output", "repair"])]
+    pub timestamp_dir: bool,
+
+    /// strftime format of the --timestamp-dir name.
+    #[arg(long, value_name = "FORMAT", default_value = "%Y-%m-%dT%H-%M-%S", requires = "timestamp_dir")]
+    pub timestamp_format: String,
+
+    /// Shell command that names each file: it gets the URL and default filename on stdin and prints
+    /// the name to use (relative to the download directory); empty output or failure keeps the default.
+    #[arg(long, value_name = "CMD", conflicts_with = "output")]
+    pub name_command: Option<String>,
+
+    /// Save the single download as this file; `-` streams it to stdout for use in pipelines.
+    #[arg(short = 'O', long, value_name = "FILE", conflicts_with_all = ["jsonl", "cas"])]
+    pub output: Option<PathBuf>,
+
+    /// Credentials for one host: HOST=USER:PASSWORD (basic) or HOST=bearer:TOKEN (repeatable; globs allowed).
+    #[arg(long = "auth", value_name = "HOST=CREDENTIALS", value_parser = crate::util::parse_host_auth)]
+    pub auth: Vec<crate::util::HostAuth>,
+
+    /// Only contact these hosts (repeatable; globs like '*.example.com' allowed).
+    #[arg(long, value_name = "HOST")]
+    pub allow_host: Vec<String>,
+
+    /// Never contact these hosts (repeatable; globs allowed). Takes precedence over --allow-host.
+    #[arg(long, value_name = "HOST")]
+    pub deny_host: Vec<String>,
+
+    /// Don't download files that already exist in DIR (same path or name, matching size and SHA-256 when known).
+    #[arg(long, value_name = "DIR", conflicts_with = "repair")]
+    pub exclude_existing_in: Option<PathBuf>,
+
+    /// With --exclude-existing-in, hardlink (or symlink/copy) the existing files into the output directory.
+    #[arg(long, requires = "exclude_existing_in")]
+    pub link_existing: bool,
+
+    /// Re-download existing files that are smaller than the remote file instead of resuming them;
+    /// files of equal or larger size are left alone.
+    #[arg(long)]
+    pub overwrite_if_smaller: bool,
+
     /// Path to a text file containing URLs to download (one per line).
     #[arg(short, long)]
     pub file: Option<PathBuf>,
 
+    /// JSON file with an array of {url, filename, sha256, mirrors, headers} objects to download.
+    #[arg(long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
     /// Hugging Face repository ID (e.g., 'TheBloke/Llama-2-7B-GGUF') or URL.
     #[arg(short = 'H', long)]
     pub hf: Option<String>,
@@ -50,19 +199,126 @@ pub struct Cli {
     #[arg(short, long)]
     pub model: Option<String>,
 
+    /// Only download repo files matching this glob (repeatable; Hugging Face only).
+    #[arg(long, value_name = "GLOB", requires = "hf")]
+    pub include: Vec<String>,
+
+    /// Skip repo files matching this glob (repeatable; Hugging Face only).
+    #[arg(long, value_name = "GLOB", requires = "hf")]
+    pub exclude: Vec<String>,
+
+    /// Download only the highest-versioned file matching this glob, e.g. 'model-v*.gguf' (Hugging Face only).
+    #[arg(long, value_name = "GLOB", requires = "hf", conflicts_with_all = ["select", "auto_select"])]
+    pub latest: Option<String>,
+
+    /// Shortcut for --include '*.gguf'.
+    #[arg(long, requires = "hf")]
+    pub gguf: bool,
+
+    /// Shortcut for --include '*.safetensors' plus safetensors index files.
+    #[arg(long, requires = "hf")]
+    pub safetensors: bool,
+
+    /// Shortcut that includes only config and tokenizer files (*.json, tokenizer*, ...).
+    #[arg(long, requires = "hf")]
+    pub only_config: bool,
+
     /// Interactively select GGUF files from a Hugging Face repository.
     #[arg(short = 's', long)]
     pub select: bool,
 
+    /// Hugging Face endpoint for API and file URLs (defaults to HF_ENDPOINT or https://huggingface.co).
+    #[arg(long, global = true)]
+    pub endpoint: Option<String>,
+
+    /// Sort the GGUF selection list by quantization type instead of by name.
+    #[arg(long, requires = "select")]
+    pub group_by_quant: bool,
+
+    /// Pick the smallest or largest complete GGUF file/series without prompting (Hugging Face only).
+    #[arg(long, value_enum, requires = "hf")]
+    pub auto_select: Option<AutoSelect>,
+
+    /// Refuse TLS connections below this protocol version (defaults to the TLS library's minimum).
+    #[arg(long, value_enum, value_name = "VERSION", global = true)]
+    pub min_tls: Option<MinTls>,
+
+    /// Give up on a metadata request (repo listing, search, release info) after this many seconds; 0 disables the limit.
+    #[arg(long, value_name = "SECS", default_value_t = 30, global = true)]
+    pub api_timeout: u64,
+
+    /// Reuse cached Hugging Face repo listings and search results younger than this, e.g. 10m, 1h or 2d.
+    #[arg(long, value_name = "DURATION", value_parser = crate::util::parse_duration, global = true)]
+    pub max_age: Option<std::time::Duration>,
+
+    /// Don't read or write the metadata cache (overrides --max-age).
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Try IPv4 addresses before IPv6 ones (falling back to IPv6 if IPv4 fails).
+    #[arg(long, global = true, conflicts_with = "prefer_ipv6")]
+    pub prefer_ipv4: bool,
+
+    /// Try IPv6 addresses before IPv4 ones (falling back to IPv4 if IPv6 fails).
+    #[arg(long, global = true)]
+    pub prefer_ipv6: bool,
+
     /// Use HF_TOKEN environment variable for Hugging Face requests.
     #[arg(long)]
     pub token: bool,
 
+    /// Remove orphaned temp files (.part/.new) in the output directory without prompting.
+    #[arg(long)]
+    pub clean: bool,
+
+    /// Store files by SHA-256 in this directory and link them into the download location.
+    #[arg(long, value_name = "DIR")]
+    pub cas: Option<PathBuf>,
+
+    /// Periodically write a JSON snapshot of the run's progress to this file.
+    #[arg(long, value_name = "PATH")]
+    pub status_file: Option<PathBuf>,
+
+    /// Keep HTML responses even when the file isn't expected to be HTML.
+    #[arg(long)]
+    pub allow_html: bool,
+
+    /// Show only the overall bar and one line listing the active downloads, instead of a bar per file.
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Stream one JSON object per finished file to stdout (progress stays on stderr).
+    #[arg(long)]
+    pub jsonl: bool,
+
     /// Enable debug logging to log.log.
     #[arg(long)]
     pub debug: bool,
 }
 
+/// Which GGUF file or series `--auto-select` picks.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoSelect {
+    Smallest,
+    Largest,
+}
+
+/// Lowest TLS version `--min-tls` allows.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MinTls {
+    #[value(name = "1.2")]
+    Tls12,
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+/// Address family tried first when a host has both IPv4 and IPv6 addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpPreference {
+    V4,
+    V6,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Manage Hugging Face models.
@@ -70,9 +326,36 @@ pub enum Commands {
         #[command(subcommand)]
         command: ModelCommands,
     },
+    /// Show a status file written by a run using --status-file.
+    Status {
+        #[arg(help = "Path to the status file")]
+        file: PathBuf,
+    },
+    /// Show version and build details.
+    Version {
+        /// Print them as a JSON object.
+        #[arg(long)]
+        json: bool,
+    },
     /// Check for and apply application self-updates.
     #[command(name = "update")]
-    UpdateApp,
+    UpdateApp {
+        /// GitHub token for API requests, raising the rate limit (defaults to GITHUB_TOKEN).
+        #[arg(long)]
+        github_token: Option<String>,
+
+        /// List the release's assets and which one matches this platform, without updating.
+        #[arg(long)]
+        list_assets: bool,
+
+        /// Release tag to list instead of the latest (with --list-assets).
+        #[arg(long = "version", value_name = "TAG", requires = "list_assets")]
+        release: Option<String>,
+
+        /// Show the notes of every release newer than this version, then ask before updating.
+        #[arg(long, conflicts_with = "list_assets")]
+        since_release: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -81,5 +364,20 @@ pub enum ModelCommands {
     Search {
         #[arg(required = true, help = "The search term for models")]
         query: Vec<String>,
+
+        /// Only show repos that contain at least one .gguf file.
+        #[arg(long)]
+        has_gguf: bool,
     },
-}
\ No newline at end of file
+    /// Print the registry aliases usable with -m, one per line (for shell completion).
+    #[command(hide = true)]
+    Aliases,
+}
+
+fn parse_chunk_size(value: &str) -> anyhow::Result<usize> {
+    let size = crate::util::parse_size(value)?;
+    if size == 0 {
+        return Err(anyhow::anyhow!("chunk size must be greater than zero"));
+    }
+    Ok(size as usize)
+}
@@ -4,30 +4,65 @@ use std::collections::HashMap;
 pub const CURRENT_APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const DEVELOPMENT_VERSION: &str = "DEVELOPMENT"; // Used for local builds not matching a git tag
 
+// Build details embedded by build.rs
+pub const BUILD_TARGET: &str = env!("DL_TARGET");
+pub const GIT_COMMIT: Option<&str> = option_env!("DL_GIT_COMMIT");
+
 // Updater constants
 pub const UPDATER_REPO_OWNER: &str = "vyrti";
 pub const UPDATER_REPO_NAME: &str = "dl-rust";
 
+// Hugging Face endpoint used when neither --endpoint nor HF_ENDPOINT is set
+pub const DEFAULT_HF_ENDPOINT: &str = "https://huggingface.co";
+
+/// Where a registry alias downloads from.
+#[derive(Debug, Clone, Copy)]
+pub enum ModelSource {
+    /// A single hard-coded file URL.
+    Url(&'static str),
+    /// Files in a Hugging Face repo whose name matches the glob (`*` and `?` wildcards).
+    Repo {
+        repo: &'static str,
+        pattern: &'static str,
+    },
+}
+
 lazy_static! {
-    pub static ref MODEL_REGISTRY: HashMap<&'static str, &'static str> = {
+    pub static ref MODEL_REGISTRY: HashMap<&'static str, ModelSource> = {
         let mut m = HashMap::new();
-        m.insert("qwen3-0.6b", "https://huggingface.co/Qwen/Qwen3-4B-GGUF/resolve/main/Qwen3-4B-Q4_K_M.gguf?download=true");
-        m.insert("qwen3-1.7b", "https://huggingface.co/Qwen/Qwen3-8B-GGUF/resolve/main/Qwen3-8B-Q4_K_M.gguf?download=true");
-        m.insert("qwen3-4b", "https://huggingface.co/Qwen/Qwen3-4B-GGUF/resolve/main/Qwen3-4B-Q4_K_M.gguf?download=true");
-        m.insert("qwen3-8b", "https://huggingface.co/Qwen/Qwen3-8B-GGUF/resolve/main/Qwen3-8B-Q4_K_M.gguf?download=true");
-        m.insert("qwen3-16b", "https://huggingface.co/Qwen/Qwen3-16B-GGUF/resolve/main/Qwen3-16B-Q4_K_M.gguf?download=true");
-        m.insert("qwen3-32b", "https://huggingface.co/Qwen/Qwen3-32B-GGUF/resolve/main/Qwen3-32B-Q4_K_M.gguf?download=true");
-        m.insert("qwen3-30b-moe", "https://huggingface.co/Qwen/Qwen3-16B-GGUF/resolve/main/Qwen3-16B-Q4_K_M.gguf?download=true");
-        m.insert("gemma3-27b", "https://huggingface.co/unsloth/gemma-3-27b-it-GGUF/resolve/main/gemma-3-27b-it-Q4_0.gguf?download=true");
+        m.insert("qwen3-0.6b", ModelSource::Url("https://huggingface.co/Qwen/Qwen3-4B-GGUF/resolve/main/Qwen3-4B-Q4_K_M.gguf?download=true"));
+        m.insert("qwen3-1.7b", ModelSource::Url("https://huggingface.co/Qwen/Qwen3-8B-GGUF/resolve/main/Qwen3-8B-Q4_K_M.gguf?download=true"));
+        m.insert("qwen3-4b", ModelSource::Repo { repo: "Qwen/Qwen3-4B-GGUF", pattern: "*Q4_K_M*.gguf" });
+        m.insert("qwen3-8b", ModelSource::Repo { repo: "Qwen/Qwen3-8B-GGUF", pattern: "*Q4_K_M*.gguf" });
+        m.insert("qwen3-16b", ModelSource::Url("https://huggingface.co/Qwen/Qwen3-16B-GGUF/resolve/main/Qwen3-16B-Q4_K_M.gguf?download=true"));
+        m.insert("qwen3-32b", ModelSource::Repo { repo: "Qwen/Qwen3-32B-GGUF", pattern: "*Q4_K_M*.gguf" });
+        m.insert("qwen3-30b-moe", ModelSource::Url("https://huggingface.co/Qwen/Qwen3-16B-GGUF/resolve/main/Qwen3-16B-Q4_K_M.gguf?download=true"));
+        m.insert("gemma3-27b", ModelSource::Url("https://huggingface.co/unsloth/gemma-3-27b-it-GGUF/resolve/main/gemma-3-27b-it-Q4_0.gguf?download=true"));
         m
     };
 }
 
-pub fn get_model_registry() -> &'static HashMap<&'static str, &'static str> {
+pub fn get_model_registry() -> &'static HashMap<&'static str, ModelSource> {
     &MODEL_REGISTRY
 }
 
+// Include patterns behind the --gguf, --safetensors and --only-config shortcuts.
+pub const GGUF_PATTERNS: &[&str] = &["*.gguf"];
+pub const SAFETENSORS_PATTERNS: &[&str] = &["*.safetensors", "*.safetensors.index.json"];
+pub const CONFIG_PATTERNS: &[&str] = &[
+    "*.json",
+    "tokenizer*",
+    "*.tiktoken",
+    "merges.txt",
+    "vocab.txt",
+];
+
 lazy_static! {
     pub static ref GGUF_SERIES_REGEX: regex::Regex =
         regex::Regex::new(r"^(.*?)-(\d{5})-of-(\d{5})\.gguf$").unwrap();
+
+    // Quantization tokens in GGUF filenames, e.g. Q4_K_M, Q8_0, IQ4_XS, TQ1_0, BF16.
+    pub static ref GGUF_QUANT_REGEX: regex::Regex = regex::Regex::new(
+        r"(?i)(?:^|[-._])(I?Q\d+(?:_[0-9A-Z]+)*|TQ\d+_\d+|MXFP4|BF16|F16|F32)(?:[-.]|$)"
+    ).unwrap();
 }
\ No newline at end of file
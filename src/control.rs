@@ -0,0 +1,223 @@
+use crate::downloader::truncate_filename;
+use crate::progress::{print_message, RunTracker};
+use console::Key;
+use indicatif::ProgressBar;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Default)]
+struct ControlState {
+    paused: bool,
+    /// Task ids the user asked to skip.
+    skipped: HashSet<usize>,
+}
+
+/// The download was skipped from the keyboard while it was running.
+#[derive(Debug)]
+pub struct SkippedByUserError;
+
+impl std::fmt::Display for SkippedByUserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Skipped from the keyboard")
+    }
+}
+
+impl std::error::Error for SkippedByUserError {}
+
+/// Pause and skip requests shared between the keyboard reader and the download tasks.
+pub struct RunControl {
+    state: watch::Sender<ControlState>,
+}
+
+impl RunControl {
+    pub fn new() -> Self {
+        RunControl {
+            state: watch::Sender::new(ControlState::default()),
+        }
+    }
+
+    /// The handle one download task checks its pause and skip state through.
+    pub fn for_task(&self, id: usize) -> TaskControl {
+        TaskControl {
+            id,
+            receiver: tokio::sync::Mutex::new(self.state.subscribe()),
+        }
+    }
+
+    pub fn is_skipped(&self, id: usize) -> bool {
+        self.state.borrow().skipped.contains(&id)
+    }
+
+    fn set_paused(&self, paused: bool) -> bool {
+        self.state.send_if_modified(|s| std::mem::replace(&mut s.paused, paused) != paused)
+    }
+
+    fn skip(&self, id: usize) {
+        self.state.send_modify(|s| {
+            s.skipped.insert(id);
+        });
+    }
+}
+
+/// One task's view of the run's control state, subscribed once for the task's lifetime.
+pub struct TaskControl {
+    id: usize,
+    receiver: tokio::sync::Mutex<watch::Receiver<ControlState>>,
+}
+
+impl TaskControl {
+    /// Called before each read from the network: waits while the run is paused and fails once
+    /// the download has been skipped.
+    pub async fn checkpoint(&self) -> Result<(), SkippedByUserError> {
+        let id = self.id;
+        let mut receiver = self.receiver.lock().await;
+        let skipped = match receiver.wait_for(|s| !s.paused || s.skipped.contains(&id)).await {
+            Ok(state) => state.skipped.contains(&id),
+            Err(_) => false,
+        };
+        if skipped {
+            Err(SkippedByUserError)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// How long the reader waits for a key before checking whether it should stop.
+#[cfg(unix)]
+const KEY_POLL_INTERVAL_MS: libc::c_int = 100;
+
+/// Reads `p`/`r`/`s` from the terminal until dropped.
+pub struct KeyboardControl {
+    stop: Arc<AtomicBool>,
+    reader: Option<std::thread::JoinHandle<()>>,
+    #[cfg(unix)]
+    original_termios: Option<libc::termios>,
+}
+
+/// Starts reading single key presses in raw mode. Only call this when stdin and stderr are terminals.
+pub fn spawn_keyboard_control(control: Arc<RunControl>, tracker: Arc<RunTracker>) -> KeyboardControl {
+    let stop = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    let original_termios = current_termios();
+    #[cfg(unix)]
+    if let Some(termios) = original_termios {
+        // Deliver key presses one at a time and without echo for the whole run, so they can be
+        // polled for; signals such as Ctrl+C keep working.
+        let mut single_keys = termios;
+        single_keys.c_lflag &= !(libc::ICANON | libc::ECHO);
+        single_keys.c_cc[libc::VMIN] = 1;
+        single_keys.c_cc[libc::VTIME] = 0;
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &single_keys);
+        }
+    }
+    print_message("[INFO] Press p to pause, r to resume, s to skip the slowest download.");
+    // A plain thread rather than spawn_blocking, so a read in progress never holds up the runtime.
+    let reader_stop = stop.clone();
+    let reader = std::thread::spawn(move || {
+        let stop = reader_stop;
+        let mut paused_bars: Vec<(ProgressBar, String)> = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            let key = match read_key() {
+                Ok(Some(key)) => key,
+                Ok(None) => continue,
+                Err(_) => break,
+            };
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            match key {
+                Key::Char('p' | 'P') if control.set_paused(true) => {
+                    tracker.for_each_active(|download| {
+                        let bar = download.progress_bar.clone();
+                        paused_bars.push((bar.clone(), bar.message()));
+                        bar.set_message(format!("{} [Paused]", truncate_filename(&download.name, 20)));
+                    });
+                    print_message("[INFO] Paused. Press r to resume.");
+                }
+                Key::Char('r' | 'R') if control.set_paused(false) => {
+                    for (bar, message) in paused_bars.drain(..) {
+                        bar.set_message(message);
+                    }
+                    print_message("[INFO] Resumed.");
+                }
+                Key::Char('s' | 'S') => match tracker.slowest_active(|id| !control.is_skipped(id)) {
+                    Some((id, name)) => {
+                        control.skip(id);
+                        print_message(format!("[INFO] Skipping {}.", name));
+                    }
+                    None => print_message("[INFO] No running download to skip."),
+                },
+                _ => {}
+            }
+        }
+    });
+    KeyboardControl {
+        stop,
+        reader: Some(reader),
+        #[cfg(unix)]
+        original_termios,
+    }
+}
+
+/// Waits briefly for a key press, so the reader can notice it was stopped. `Ok(None)` on timeout.
+#[cfg(unix)]
+fn read_key() -> std::io::Result<Option<Key>> {
+    let mut stdin = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut stdin, 1, KEY_POLL_INTERVAL_MS) } {
+        0 => return Ok(None),
+        n if n < 0 => {
+            let err = std::io::Error::last_os_error();
+            return if err.kind() == std::io::ErrorKind::Interrupted { Ok(None) } else { Err(err) };
+        }
+        _ => {}
+    }
+    let mut byte = 0u8;
+    match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+        1 => Ok(Some(Key::Char(byte as char))),
+        0 => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// The console has no way to wait for a key with a timeout, so on Windows the read blocks; the
+/// reader then stops at the next key press after the run ends.
+#[cfg(not(unix))]
+fn read_key() -> std::io::Result<Option<Key>> {
+    console::Term::stderr().read_key().map(Some)
+}
+
+impl Drop for KeyboardControl {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // On Unix the reader wakes up within one poll interval, so no later key press is consumed.
+        if let Some(reader) = self.reader.take() {
+            if cfg!(unix) {
+                let _ = reader.join();
+            }
+        }
+        #[cfg(unix)]
+        if let Some(termios) = self.original_termios {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn current_termios() -> Option<libc::termios> {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } == 0 {
+        Some(unsafe { termios.assume_init() })
+    } else {
+        None
+    }
+}
@@ -1,63 +1,617 @@
 use crate::{
-    config::GGUF_SERIES_REGEX,
+    cas,
+    checksum::{sha256_file_async, verify_sha256, ChecksumMismatchError},
+    cli::AutoSelect,
+    control::{spawn_keyboard_control, RunControl, SkippedByUserError, TaskControl},
+    progress::{
+        activate_progress, format_progress_line, print_message, spawn_compact_line, spawn_eta_monitor, spawn_text_progress,
+        stderr_is_interactive,
+        ActiveDownload, RunTracker,
+    },
+    status::{spawn_status_writer, write_status_file, StatusSnapshot},
+    cleanup::clean_orphaned_temp_files,
+    config::{GGUF_QUANT_REGEX, GGUF_SERIES_REGEX},
     hf::HFFile,
-    util::{format_bytes, format_duration_human, generate_actual_filename, get_client, shorten_error},
+    util::{
+        create_dir_checked, format_bytes, format_duration_human, generate_actual_filename, get_client, headers_for_url,
+        path_matches_glob, same_host, shorten_error, ClientOptions, HostAuth,
+    },
 };
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use futures_util::stream::StreamExt;
 use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
 use log::{debug, error, info};
-use std::collections::HashMap;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write as FmtWrite;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{
-    atomic::{AtomicUsize, Ordering},
+    atomic::{AtomicU64, AtomicUsize, Ordering},
     Arc, Mutex,
 };
+use std::time::{Duration, Instant};
 
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
 // A dedicated, higher concurrency level for fetching metadata.
 // This is much faster than the default download concurrency of 3.
-const PRESCAN_CONCURRENCY: usize = 20;
+pub const PRESCAN_CONCURRENCY: usize = 20;
 
-#[derive(Debug)]
+// The overall bar drops its percentage when at least this share of files has no known size.
+const UNSIZED_SPINNER_FRACTION: f64 = 0.25;
+
+// On metered connections, runs larger than this need explicit confirmation.
+const METERED_CONFIRM_THRESHOLD: u64 = 1_000_000_000;
+
+// Backoff before the first retry; doubled for each further attempt up to the cap.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// DNS and connection-refused failures either clear up quickly or not at all, so they back off less.
+const CONNECT_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+const CONNECT_RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
 pub struct DownloadItem {
     pub url: String,
     pub preferred_filename: Option<String>,
+    /// Expected SHA-256 of the file, when the source provides one.
+    pub sha256: Option<String>,
+    /// Size declared by the source (e.g. the HF API); such items skip the prescan.
+    pub size: Option<u64>,
+    /// Alternative URLs for the same file, tried in turn on retries after `url` fails.
+    pub mirrors: Vec<String>,
+    /// Extra request headers for this item only.
+    pub headers: HeaderMap,
+    /// Where `sha256` came from, for reporting.
+    pub checksum_source: Option<ChecksumSource>,
+    /// Higher priorities are started first; equal priorities keep their order.
+    pub priority: i32,
+}
+
+/// The source that declared a file's expected SHA-256.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumSource {
+    /// LFS metadata from the Hugging Face API.
+    HuggingFace,
+    Manifest,
+    /// A `sha256sum`-style file given with `--checksums-url`.
+    ChecksumsFile,
+}
+
+impl ChecksumSource {
+    fn label(self) -> &'static str {
+        match self {
+            ChecksumSource::HuggingFace => "Hugging Face",
+            ChecksumSource::Manifest => "manifest",
+            ChecksumSource::ChecksumsFile => "checksums file",
+        }
+    }
+}
+
+impl DownloadItem {
+    /// The file's path as named by its source: the preferred filename, else the URL's last segment.
+    pub fn source_name(&self) -> String {
+        match &self.preferred_filename {
+            Some(name) => name.clone(),
+            None => url::Url::parse(&self.url)
+                .ok()
+                .and_then(|u| u.path_segments().and_then(|mut s| s.next_back().map(str::to_string)))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Request headers for `url`, the primary URL or one of the mirrors. The item's own headers
+    /// (which may carry credentials) only go to the primary URL's host; every request gets the
+    /// `--auth` credentials for its own host.
+    fn request_headers(&self, url: &str, auth: &[HostAuth]) -> HeaderMap {
+        if same_host(url, &self.url) {
+            headers_for_url(url, &self.headers, auth)
+        } else {
+            if !self.headers.is_empty() {
+                debug!("Not sending {}'s headers to mirror {} on another host", self.url, url);
+            }
+            headers_for_url(url, &HeaderMap::new(), auth)
+        }
+    }
+
+    /// The URL used for a given (1-based) attempt: the primary first, then each mirror in turn.
+    fn url_for_attempt(&self, attempt: u32) -> &str {
+        let sources = 1 + self.mirrors.len();
+        match attempt.saturating_sub(1) as usize % sources {
+            0 => &self.url,
+            i => &self.mirrors[i - 1],
+        }
+    }
+}
+
+/// Options controlling a download run, built from the command line.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub concurrency: usize,
+    pub hf_token: String,
+    /// Remove orphaned temp files in the output directory without prompting.
+    pub clean: bool,
+    /// Stream one JSON object per finished file to stdout.
+    pub jsonl: bool,
+    /// Content-addressed store; files are stored by hash and linked into place.
+    pub cas_dir: Option<PathBuf>,
+    /// How many times a failed download is retried before giving up.
+    pub retries: u32,
+    /// Whether DNS and connection-refused failures are retried (with a shorter backoff).
+    pub retry_connect_errors: bool,
+    /// Periodically write a JSON snapshot of the run to this path.
+    pub status_file: Option<PathBuf>,
+    /// Keep `text/html` responses even when the destination isn't an HTML file.
+    pub allow_html: bool,
+    /// Incoming bytes are buffered up to this size before each disk write and progress update.
+    pub chunk_size: usize,
+    /// How many size requests the prescan runs at once.
+    pub prescan_concurrency: usize,
+    /// Ask before downloading large runs, for expensive connections.
+    pub metered: bool,
+    /// Cancel the remaining downloads and fail the run on the first failed file.
+    pub fail_fast: bool,
+    /// Report 404/410 responses as missing files instead of failures.
+    pub skip_missing: bool,
+    /// Maximum number of parallel range requests per file.
+    pub split: usize,
+    /// Smallest part a split download is allowed to produce.
+    pub min_split_size: u64,
+    /// Destinations matching these globs are re-downloaded even if already present.
+    pub force_files: Vec<String>,
+    /// Re-download existing files smaller than the remote size instead of resuming them.
+    pub overwrite_if_smaller: bool,
+    /// Hard ceiling on a single download attempt, regardless of connection activity.
+    pub timeout_per_file: Option<Duration>,
+    /// Prescan every file, even those whose size the source already declared.
+    pub force_prescan: bool,
+    /// Stream the (single) download to stdout instead of writing it to disk.
+    pub to_stdout: bool,
+    /// Re-read each finished file from disk and check its size and, if known, its hash.
+    pub verify_readback: bool,
+    /// Print what would be downloaded, with sizes and checksum availability, and stop.
+    pub dry_run: bool,
+    /// Verify every file's SHA-256 and fail files that have no known checksum.
+    pub strict_verify: bool,
+    /// Keep only the N largest or smallest files, ranked by their prescanned sizes.
+    pub top_files: Option<TopFiles>,
+    /// Per-host credentials, attached only to requests for the matching host.
+    pub host_auth: Vec<HostAuth>,
+    /// Once a write fails because the disk is full, let running downloads finish but start no more.
+    pub continue_on_disk_full: bool,
+    /// Show one line listing the active downloads instead of a bar per file.
+    pub compact: bool,
+    /// Reference directory whose matching files are not downloaded again.
+    pub exclude_existing_in: Option<PathBuf>,
+    /// Link such files into the destination rather than only skipping them.
+    pub link_existing: bool,
+    /// How often a file whose checksum doesn't match is deleted and downloaded again.
+    pub checksum_retries: u32,
+    /// Cap on the summed size of the selected files.
+    pub max_total_size: Option<u64>,
+    /// TLS and IP settings for every HTTP client the run creates.
+    pub client_options: ClientOptions,
+}
+
+/// Which end of the size ranking `--top-largest` / `--top-smallest` keep.
+#[derive(Debug, Clone, Copy)]
+pub enum TopFiles {
+    Largest(usize),
+    Smallest(usize),
+}
+
+/// How a single download ended.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Completed,
+    Skipped,
+    /// Linked from the content-addressed store without transferring anything.
+    Linked,
+    Failed,
+    /// Cancelled by `--timeout-per-file` on the last attempt.
+    TimedOut,
+    /// The server answered 404 or 410 and `--skip-missing` is set.
+    Missing,
+    /// Skipped from the keyboard (`s`) while it was running; the partial file is kept for resuming.
+    Cancelled,
+    /// Written successfully, but `--verify-readback` found the file on disk doesn't match.
+    ReadbackFailed,
+    /// Never started because the disk filled up and `--continue-on-disk-full` stopped new downloads.
+    NotStarted,
+}
+
+impl DownloadStatus {
+    fn is_failure(self) -> bool {
+        matches!(
+            self,
+            DownloadStatus::Failed
+                | DownloadStatus::TimedOut
+                | DownloadStatus::ReadbackFailed
+                | DownloadStatus::NotStarted
+        )
+    }
+}
+
+/// Broad cause of a failed download; picks the retry policy and is shown in the summary.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The host name could not be resolved.
+    Dns,
+    ConnectionRefused,
+    /// The server answered with a 4xx status.
+    HttpClientError,
+    /// The server answered with a 5xx status.
+    HttpServerError,
+    /// The file's SHA-256 didn't match, or `--strict-verify` found no checksum to check.
+    Checksum,
+    /// A write failed because the disk (or the user's quota) is full.
+    DiskFull,
+    Other,
+}
+
+impl FailureKind {
+    fn label(self) -> &'static str {
+        match self {
+            FailureKind::Dns => "DNS lookup failed",
+            FailureKind::ConnectionRefused => "connection refused",
+            FailureKind::HttpClientError => "HTTP client error",
+            FailureKind::HttpServerError => "HTTP server error",
+            FailureKind::Checksum => "checksum verification failed",
+            FailureKind::DiskFull => "disk full",
+            FailureKind::Other => "error",
+        }
+    }
+
+    fn is_connect_error(self) -> bool {
+        matches!(self, FailureKind::Dns | FailureKind::ConnectionRefused)
+    }
+}
+
+/// The HTTP status that caused the error, if it came from an error response.
+fn http_status(err: &anyhow::Error) -> Option<reqwest::StatusCode> {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .find_map(reqwest::Error::status)
+}
+
+/// Classifies an error by walking its cause chain for HTTP statuses, refused connections and
+/// resolver failures (which hyper only reports by message).
+fn classify_failure(err: &anyhow::Error) -> FailureKind {
+    for cause in err.chain() {
+        if cause.is::<ChecksumMismatchError>() || cause.is::<MissingChecksumError>() {
+            return FailureKind::Checksum;
+        }
+        if cause.is::<DiskFullDrainError>()
+            || cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                matches!(e.kind(), std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded)
+            })
+        {
+            return FailureKind::DiskFull;
+        }
+        if let Some(status) = cause.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) {
+            if status.is_client_error() {
+                return FailureKind::HttpClientError;
+            }
+            if status.is_server_error() {
+                return FailureKind::HttpServerError;
+            }
+        }
+        if cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::ConnectionRefused)
+        {
+            return FailureKind::ConnectionRefused;
+        }
+        let message = cause.to_string().to_ascii_lowercase();
+        if message.contains("dns error") || message.contains("failed to lookup address") {
+            return FailureKind::Dns;
+        }
+    }
+    FailureKind::Other
+}
+
+/// The outcome of one file, as reported by `--jsonl`.
+#[derive(Serialize, Debug, Clone)]
+pub struct DownloadResult {
+    pub url: String,
+    pub path: PathBuf,
+    pub status: DownloadStatus,
+    /// Bytes transferred during this run (excluding any previously downloaded prefix).
+    pub bytes: u64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    /// `bytes / duration_secs`, covering retries and backoff as well as transfer time.
+    pub avg_bytes_per_sec: f64,
+    pub attempts: u32,
+    pub error: Option<String>,
+    /// Why the download failed, for failed and timed-out files.
+    pub failure: Option<FailureKind>,
+    /// Where the expected SHA-256 came from, if the file had one.
+    pub checksum_source: Option<ChecksumSource>,
+    /// Times the file was deleted and downloaded again after a checksum mismatch.
+    pub checksum_redownloads: u32,
+}
+
+/// Totals for a whole run, printed at the end and emitted as the last `--jsonl` line.
+#[derive(Serialize, Debug, Clone)]
+pub struct RunSummary {
+    pub files: usize,
+    pub completed: usize,
+    pub skipped: usize,
+    pub linked: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    /// Files skipped by `--skip-missing` because the server reported them gone.
+    pub missing: usize,
+    /// Downloads skipped from the keyboard while running.
+    pub cancelled: usize,
+    /// Files that downloaded but failed the `--verify-readback` check.
+    pub readback_failed: usize,
+    /// Files `--continue-on-disk-full` never started after the disk filled up.
+    pub not_started: usize,
+    /// Files that failed their checksum but matched after `--retry-on-checksum-mismatch` re-downloaded them.
+    pub checksum_recovered: usize,
+    /// Bytes transferred across all files, excluding previously downloaded prefixes.
+    pub bytes: u64,
+    pub duration_secs: f64,
+    pub avg_bytes_per_sec: f64,
+    /// Highest aggregate rate over a one-second sampling interval.
+    pub peak_bytes_per_sec: Option<f64>,
+}
+
+impl RunSummary {
+    fn from_results(results: &[DownloadResult], duration: Duration, peak_bytes_per_sec: Option<f64>) -> Self {
+        let count = |status: DownloadStatus| results.iter().filter(|r| r.status == status).count();
+        let bytes = results.iter().map(|r| r.bytes).sum();
+        let duration_secs = duration.as_secs_f64();
+        RunSummary {
+            files: results.len(),
+            completed: count(DownloadStatus::Completed),
+            skipped: count(DownloadStatus::Skipped),
+            linked: count(DownloadStatus::Linked),
+            failed: count(DownloadStatus::Failed),
+            timed_out: count(DownloadStatus::TimedOut),
+            missing: count(DownloadStatus::Missing),
+            cancelled: count(DownloadStatus::Cancelled),
+            readback_failed: count(DownloadStatus::ReadbackFailed),
+            not_started: count(DownloadStatus::NotStarted),
+            checksum_recovered: results
+                .iter()
+                .filter(|r| r.checksum_redownloads > 0 && !r.status.is_failure())
+                .count(),
+            bytes,
+            duration_secs,
+            avg_bytes_per_sec: average_rate(bytes, duration_secs),
+            peak_bytes_per_sec,
+        }
+    }
+}
+
+/// Wraps the run summary so the final `--jsonl` line is told apart from per-file results.
+#[derive(Serialize)]
+struct SummaryLine<'a> {
+    summary: &'a RunSummary,
+}
+
+fn average_rate(bytes: u64, duration_secs: f64) -> f64 {
+    if duration_secs > 0.0 {
+        bytes as f64 / duration_secs
+    } else {
+        0.0
+    }
+}
+
+/// The server answered with an HTML page (typically an error or login page) instead of the file.
+#[derive(Debug)]
+struct UnexpectedHtmlError {
+    url: String,
+    preview: String,
+}
+
+impl std::fmt::Display for UnexpectedHtmlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let snippet: String = self
+            .preview
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .take(80)
+            .collect();
+        write!(
+            f,
+            "Server returned an HTML page instead of the file for {} (use --allow-html to keep it). Page starts with: {}",
+            self.url, snippet
+        )
+    }
+}
+
+impl std::error::Error for UnexpectedHtmlError {}
+
+/// `--strict-verify` is set but no source declared a checksum for the file.
+#[derive(Debug)]
+struct MissingChecksumError;
+
+impl std::fmt::Display for MissingChecksumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No checksum is available for this file (required by --strict-verify)")
+    }
+}
+
+impl std::error::Error for MissingChecksumError {}
+
+/// The download was held back because an earlier write found the disk full.
+#[derive(Debug)]
+struct DiskFullDrainError;
+
+impl std::fmt::Display for DiskFullDrainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not started: the disk is full (--continue-on-disk-full)")
+    }
+}
+
+impl std::error::Error for DiskFullDrainError {}
+
+/// A ranged response arrived with a Content-Encoding, so its bytes don't line up with the partial file.
+#[derive(Debug)]
+struct EncodedRangeError {
+    url: String,
+    encoding: String,
+}
+
+impl std::fmt::Display for EncodedRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Refusing to resume {}: the server or a proxy applied Content-Encoding '{}' to a range request",
+            self.url, self.encoding
+        )
+    }
+}
+
+impl std::error::Error for EncodedRangeError {}
+
+/// The server answered a split part with something other than 206, so it can't serve ranges.
+#[derive(Debug)]
+struct RangeNotSupportedError;
+
+impl std::fmt::Display for RangeNotSupportedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Server does not support range requests")
+    }
+}
+
+impl std::error::Error for RangeNotSupportedError {}
+
+/// A download attempt ran past `--timeout-per-file` and was cancelled.
+#[derive(Debug)]
+struct FileTimeoutError {
+    url: String,
+    limit: Duration,
+}
+
+impl std::fmt::Display for FileTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Download of {} did not finish within {}",
+            self.url,
+            format_duration_human(self.limit, true)
+        )
+    }
+}
+
+impl std::error::Error for FileTimeoutError {}
+
+// Destination reported for downloads streamed to stdout.
+const STDOUT_DESTINATION: &str = "-";
+
+// How many files --max-total-size names when reporting what it left out.
+const MAX_LISTED_EXCLUDED: usize = 10;
+
+// How much of an unexpected HTML body is kept for the debug log.
+const HTML_PREVIEW_LEN: usize = 512;
+
+/// What `download_file` did when it succeeded.
+enum DownloadOutcome {
+    Downloaded { bytes: u64 },
+    AlreadyComplete,
+    LinkedFromStore,
+    /// A matching copy was found in `--exclude-existing-in`; `linked` if it was linked into place.
+    FoundElsewhere { path: PathBuf, linked: bool },
 }
 
 struct DownloadTask {
     item: DownloadItem,
     destination_path: PathBuf,
+    /// Discard any existing file at the destination before downloading.
+    force: bool,
     progress_bar: ProgressBar,
     overall_progress_bar: ProgressBar,
     multi_progress: Arc<MultiProgress>,
     client: reqwest::Client,
+    options: Arc<DownloadOptions>,
+    tracker: Arc<RunTracker>,
+    /// Pause and skip state, keyed by the task's index in the run (as known to the tracker).
+    control: TaskControl,
+    /// Where `--exclude-existing-in` may already hold this file, in the order they are checked.
+    existing_candidates: Vec<PathBuf>,
+}
+
+impl DownloadTask {
+    /// Request headers for `url`, which may be the primary URL or one of the mirrors.
+    fn headers_for(&self, url: &str) -> HeaderMap {
+        self.item.request_headers(url, &self.options.host_auth)
+    }
+
+    /// Connections this download opens: one, or one per part when it is split. Splitting is
+    /// capped by the run's concurrency, the budget every open connection counts against.
+    fn connection_count(&self) -> usize {
+        let total_size = self.progress_bar.length().unwrap_or(0);
+        let max_parts = self.options.split.min(self.options.concurrency.max(1));
+        split_part_count(total_size, max_parts, self.options.min_split_size)
+    }
 }
 
 pub async fn run_downloads(
-    items: Vec<DownloadItem>,
+    mut items: Vec<DownloadItem>,
     base_dir: PathBuf,
-    concurrency: usize,
-    hf_token: String,
+    options: &DownloadOptions,
 ) -> Result<()> {
-    eprintln!(
-        "[INFO] Preparing to download {} file(s) to '{}' with concurrency {}.",
-        items.len(),
-        base_dir.display(),
-        concurrency
-    );
+    let concurrency = options.concurrency;
+    let hf_token = &options.hf_token;
+    if options.to_stdout {
+        eprintln!("[INFO] Streaming {} file(s) to stdout.", items.len());
+    } else {
+        eprintln!(
+            "[INFO] Preparing to download {} file(s) to '{}' with concurrency {}.",
+            items.len(),
+            base_dir.display(),
+            concurrency
+        );
+    }
 
-    let multi_progress = Arc::new(MultiProgress::new());
+    // Bars don't render when stderr is piped; periodic text lines are printed instead.
+    let interactive = stderr_is_interactive();
+    let multi_progress = Arc::new(if interactive {
+        MultiProgress::new()
+    } else {
+        MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden())
+    });
+    // Warnings printed while this run is active go above the bars instead of through them.
+    let _progress_guard = activate_progress(&multi_progress);
     
     // --- Pre-scan for file sizes ---
-    eprintln!(
+    // Sizes declared by the source are trusted unless --force-prescan asks to double-check them.
+    let file_sizes = Arc::new(Mutex::new(HashMap::<String, u64>::new()));
+    let mut to_prescan = Vec::new();
+    for item in &items {
+        match item.size {
+            Some(size) if !options.force_prescan => {
+                file_sizes.lock().unwrap().insert(item.url.clone(), size);
+            }
+            _ => to_prescan.push(item),
+        }
+    }
+    if to_prescan.len() < items.len() {
+        debug!(
+            "Skipping prescan for {} file(s) with known sizes",
+            items.len() - to_prescan.len()
+        );
+    }
+    print_message(format!(
         "[INFO] Pre-scanning {} file(s) for sizes (this may take a moment)...",
-        items.len()
-    );
-    let prescan_bar = multi_progress.add(ProgressBar::new(items.len() as u64));
+        to_prescan.len()
+    ));
+    let prescan_bar = multi_progress.add(ProgressBar::new(to_prescan.len() as u64));
     prescan_bar.set_style(
         ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
         .expect("Invalid progress bar template")
@@ -65,29 +619,36 @@ pub async fn run_downloads(
     );
     prescan_bar.set_message("Fetching file sizes...");
 
-    let file_sizes = Arc::new(Mutex::new(HashMap::<String, u64>::new()));
     let error_count = Arc::new(AtomicUsize::new(0));
 
     // Create ONE client that will be cloned for all concurrent tasks. This is efficient and robust.
-    let prescan_client = get_client(&hf_token)?;
-    let prescan_futs = items.iter().map(|item| {
+    let prescan_client = get_client(hf_token, options.client_options)?;
+    let prescan_futs = to_prescan.into_iter().map(|item| {
         let client = prescan_client.clone(); // Use the cloned client
         let item_url = item.url.clone();
+        let declared_size = item.size;
+        let headers = item.request_headers(&item.url, &options.host_auth);
         let item_name = item.preferred_filename.as_deref().unwrap_or(&item.url).to_string();
         let prescan_bar = prescan_bar.clone();
         let file_sizes = file_sizes.clone();
         let error_count = error_count.clone();
 
         async move {
-            match fetch_file_size(&client, &item_url).await {
+            match fetch_file_size(&client, &item_url, &headers).await {
                 Ok(s) => {
+                    log_size_discrepancy(&item_name, declared_size, s);
                     file_sizes.lock().unwrap().insert(item_url, s);
                 }
                 Err(e) => {
-                    log::warn!("Prescan failed for {}: {}", item_name, e);
-                    let current_errors = error_count.fetch_add(1, Ordering::SeqCst);
-                    if current_errors < 5 {
-                         prescan_bar.println(format!("[WARN] Could not get size for '{}'. It will show as 0 B.", item_name));
+                    if let Some(size) = declared_size {
+                        debug!("Prescan failed for {}, keeping declared size {}: {}", item_name, size, e);
+                        file_sizes.lock().unwrap().insert(item_url, size);
+                    } else {
+                        log::warn!("Prescan failed for {}: {}", item_name, e);
+                        let current_errors = error_count.fetch_add(1, Ordering::SeqCst);
+                        if current_errors < 5 {
+                             print_message(format!("[WARN] Could not get size for '{}'. It will show as 0 B.", item_name));
+                        }
                     }
                 }
             }
@@ -96,9 +657,31 @@ pub async fn run_downloads(
     });
     
     let stream = futures_util::stream::iter(prescan_futs);
-    stream.buffer_unordered(PRESCAN_CONCURRENCY).for_each(|_| async {}).await;
+    stream.buffer_unordered(options.prescan_concurrency).for_each(|_| async {}).await;
     prescan_bar.finish_with_message("Pre-scan complete.");
 
+    if let Some(top) = options.top_files {
+        let sizes = file_sizes.lock().unwrap();
+        items = select_top_files(items, &sizes, top);
+        let selected_size: u64 = items.iter().filter_map(|item| sizes.get(&item.url)).sum();
+        print_message(format!(
+            "[INFO] Selected {} file(s), {} in total.",
+            items.len(),
+            format_bytes(selected_size)
+        ));
+        if items.is_empty() {
+            return Ok(());
+        }
+    }
+
+    if let Some(cap) = options.max_total_size {
+        let sizes = file_sizes.lock().unwrap();
+        items = select_within_size(items, &sizes, cap);
+        if items.is_empty() {
+            return Ok(());
+        }
+    }
+
     // --- Prepare download tasks ---
     let mut tasks = Vec::new();
     let total_download_size: u64 = items
@@ -106,16 +689,35 @@ pub async fn run_downloads(
         .map(|item| *file_sizes.lock().unwrap().get(&item.url).unwrap_or(&0))
         .sum();
     
+    if options.dry_run {
+        multi_progress.clear().ok();
+        let sizes = file_sizes.lock().unwrap();
+        print_dry_run(&items, &base_dir, &sizes, options.to_stdout);
+        return Ok(());
+    }
+
+    if options.metered && total_download_size > METERED_CONFIRM_THRESHOLD {
+        let confirmed = multi_progress.suspend(|| confirm_metered_download(total_download_size))?;
+        if !confirmed {
+            eprintln!("[INFO] Download cancelled.");
+            return Ok(());
+        }
+    }
+
     let overall_pb = multi_progress.add(ProgressBar::new(total_download_size));
-    // The Fix: Overall progress bar template now matches individual bars for consistency and custom formatting.
-    let overall_style = ProgressStyle::with_template(
-        "Overall Progress: [{bar:40.yellow/blue}] {percent:>3}% │ {bytes_formatted}/{total_bytes_formatted} @ {bytes_per_sec} │ ETA: {eta_formatted}"
-    ).expect("Invalid overall progress bar template")
-     .with_key("bytes_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_bytes(state.pos())).unwrap())
-     .with_key("total_bytes_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_bytes(state.len().unwrap_or(0))).unwrap())
-     .with_key("eta_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_duration_human(state.eta(), false)).unwrap())
-     .progress_chars("=> ");
-    overall_pb.set_style(overall_style);
+    let unsized_files = items
+        .iter()
+        .filter(|item| file_sizes.lock().unwrap().get(&item.url).copied().unwrap_or(0) == 0)
+        .count();
+    // With too many sizes missing, a percentage would be meaningless (and run past 100%), so the
+    // overall bar becomes a byte counter until the downloads have revealed every size.
+    if unsized_files > 0 && unsized_files as f64 >= items.len() as f64 * UNSIZED_SPINNER_FRACTION {
+        overall_pb.unset_length();
+        overall_pb.set_style(overall_spinner_style());
+        overall_pb.enable_steady_tick(Duration::from_millis(120));
+    } else {
+        overall_pb.set_style(overall_bar_style());
+    }
     
     // Define styles for individual bars
     let download_style = ProgressStyle::with_template(
@@ -129,11 +731,19 @@ pub async fn run_downloads(
         "{msg:30!} [ERROR: {wide_msg}]"
     ).expect("Invalid error progress bar template");
 
-    let download_client = get_client(&hf_token)?;
+    let download_client = get_client(hf_token, options.client_options)?;
+    let shared_options = Arc::new(options.clone());
+    let tracker = Arc::new(RunTracker::new(overall_pb.clone(), items.len()));
+    tracker.set_sizing(unsized_files, total_download_size);
+    let control = Arc::new(RunControl::new());
     for item in items {
         let actual_filename =
             generate_actual_filename(&item.url, item.preferred_filename.as_deref());
-        let destination_path = base_dir.join(&actual_filename);
+        let destination_path = if options.to_stdout {
+            PathBuf::from(STDOUT_DESTINATION)
+        } else {
+            base_dir.join(&actual_filename)
+        };
 
         let size = *file_sizes.lock().unwrap().get(&item.url).unwrap_or(&0);
         // Create progress bar hidden initially - it will be shown when download starts
@@ -142,64 +752,836 @@ pub async fn run_downloads(
         pb.set_style(download_style.clone());
         pb.set_message(truncate_filename(&actual_filename, 30));
 
+        let force = !options.to_stdout && options
+            .force_files
+            .iter()
+            .any(|pattern| path_matches_glob(&actual_filename, pattern));
+
+        let existing_candidates = match &options.exclude_existing_in {
+            Some(dir) if !options.to_stdout => {
+                let mut candidates = vec![dir.join(&actual_filename)];
+                if let Some(name) = Path::new(&actual_filename).file_name() {
+                    if Path::new(name) != Path::new(&actual_filename) {
+                        candidates.push(dir.join(name));
+                    }
+                }
+                candidates
+            }
+            _ => Vec::new(),
+        };
+
         tasks.push(DownloadTask {
+            existing_candidates,
             item,
             destination_path,
+            force,
             progress_bar: pb,
             overall_progress_bar: overall_pb.clone(),
             multi_progress: multi_progress.clone(),
             client: download_client.clone(),
+            options: shared_options.clone(),
+            tracker: tracker.clone(),
+            control: control.for_task(tasks.len()),
         });
     }
 
+    if !options.to_stdout {
+        // Create every destination directory once, instead of racing per-file creation in each task.
+        let needed_dirs: HashSet<&Path> = tasks
+            .iter()
+            .filter_map(|t| t.destination_path.parent())
+            .collect();
+        for dir in needed_dirs {
+            create_dir_checked(dir).await?;
+        }
+
+        let active_paths: HashSet<PathBuf> =
+            tasks.iter().map(|t| t.destination_path.clone()).collect();
+        multi_progress.suspend(|| clean_orphaned_temp_files(&base_dir, &active_paths, options.clean))?;
+    }
+
     // --- Execute downloads ---
-    let download_futs = tasks.into_iter().map(|task| {
+    let run_started = Instant::now();
+    tracker.restart_sampling();
+    let eta_monitor = spawn_eta_monitor(tracker.clone());
+    let text_progress = (!interactive).then(|| spawn_text_progress(tracker.clone()));
+    let compact_line = (interactive && options.compact).then(|| {
+        let line = multi_progress.add(ProgressBar::new_spinner());
+        line.set_style(ProgressStyle::with_template("{wide_msg}").expect("Invalid compact line template"));
+        (spawn_compact_line(tracker.clone(), line.clone()), line)
+    });
+    let status_writer = options
+        .status_file
+        .clone()
+        .map(|path| spawn_status_writer(path, tracker.clone()));
+    // Reading keys needs a terminal on both ends; piped runs can't be steered anyway.
+    let keyboard = (interactive && std::io::stdin().is_terminal())
+        .then(|| spawn_keyboard_control(control.clone(), tracker.clone()));
+
+    // Kept so --fail-fast can cancel downloads that are already running.
+    let abort_handles: Arc<Mutex<Vec<tokio::task::AbortHandle>>> = Arc::new(Mutex::new(Vec::new()));
+    let connection_slots = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    // Set by --continue-on-disk-full on the first write that finds the disk full.
+    let disk_full = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let download_futs = tasks.into_iter().enumerate().map(|(task_id, task)| {
         let url_for_log = task.item.url.clone();
+        let path_for_report = task.destination_path.clone();
         // Clone progress bar for post-download handling
         let pb_clone_for_post_download = task.progress_bar.clone();
         let error_style_clone = error_style.clone();
+        let jsonl = options.jsonl;
+        let skip_missing = options.skip_missing;
+        let readback = options.verify_readback && !options.to_stdout;
+        let strict_verify = options.strict_verify;
+        let continue_on_disk_full = options.continue_on_disk_full;
+        let disk_full = disk_full.clone();
+        let tracker = tracker.clone();
+        let abort_handles = abort_handles.clone();
+
+        let connection_slots = connection_slots.clone();
 
-        tokio::spawn(async move {
-            if let Err(e) = download_file(task).await {
-                error!("Download failed for {}: {:?}", url_for_log, e);
-                let short_err = shorten_error(&e, 40);
-                pb_clone_for_post_download.set_style(error_style_clone);
-                pb_clone_for_post_download.finish_with_message(short_err);
+        let handle = tokio::spawn(async move {
+            // A split file holds one slot per part, so open connections never exceed the concurrency.
+            let _slots = connection_slots
+                .acquire_many_owned(task.connection_count() as u32)
+                .await
+                .expect("connection semaphore is never closed");
+            let started = Instant::now();
+            let started_at = Utc::now();
+            tracker.start(task_id, ActiveDownload {
+                name: task.destination_path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                url: task.item.url.clone(),
+                progress_bar: task.progress_bar.clone(),
+            });
+            let not_started = |error: anyhow::Error| VerifiedDownload {
+                outcome: Err(error),
+                attempts: 0,
+                redownloads: 0,
+                bytes: 0,
+            };
+            let download = if disk_full.load(Ordering::Relaxed) {
+                not_started(DiskFullDrainError.into())
+            } else if strict_verify && task.item.sha256.is_none() {
+                not_started(MissingChecksumError.into())
             } else {
-                // Clear completed downloads from display
-                pb_clone_for_post_download.finish_and_clear();
+                download_verified(&task).await
+            };
+            let mut result = DownloadResult {
+                url: url_for_log,
+                path: path_for_report,
+                status: DownloadStatus::Completed,
+                bytes: download.bytes,
+                started_at,
+                finished_at: started_at,
+                duration_secs: 0.0,
+                avg_bytes_per_sec: 0.0,
+                attempts: download.attempts,
+                error: None,
+                failure: None,
+                checksum_source: task.item.checksum_source,
+                checksum_redownloads: download.redownloads,
+            };
+            match download.outcome {
+                Ok(DownloadOutcome::Downloaded { .. }) => {
+                    if readback {
+                        let expected_size = pb_clone_for_post_download.length().filter(|&l| l > 0);
+                        if let Err(e) =
+                            verify_readback(&result.path, expected_size, task.item.sha256.as_deref()).await
+                        {
+                            error!("Readback check failed for {}: {:#}", result.path.display(), e);
+                            result.status = DownloadStatus::ReadbackFailed;
+                            result.error = Some(format!("{:#}", e));
+                        }
+                    }
+                    // Clear completed downloads from display
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Ok(DownloadOutcome::AlreadyComplete) => {
+                    result.status = DownloadStatus::Skipped;
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Ok(DownloadOutcome::LinkedFromStore) => {
+                    result.status = DownloadStatus::Linked;
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Ok(DownloadOutcome::FoundElsewhere { path, linked }) => {
+                    if linked {
+                        result.status = DownloadStatus::Linked;
+                    } else {
+                        // Report (and verify) the copy that was used instead.
+                        result.status = DownloadStatus::Skipped;
+                        result.path = path;
+                    }
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Err(e) if e.downcast_ref::<DiskFullDrainError>().is_some() => {
+                    result.status = DownloadStatus::NotStarted;
+                    result.error = Some(e.to_string());
+                    result.failure = Some(FailureKind::DiskFull);
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Err(e) if e.downcast_ref::<SkippedByUserError>().is_some() => {
+                    info!("Download of {} skipped from the keyboard", result.url);
+                    result.status = DownloadStatus::Cancelled;
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Err(e) if skip_missing && is_missing(&e) => {
+                    info!("Skipping missing file {}: {:#}", result.url, e);
+                    result.status = DownloadStatus::Missing;
+                    result.error = Some(format!("{:#}", e));
+                    pb_clone_for_post_download.finish_and_clear();
+                }
+                Err(e) => {
+                    error!("Download failed for {}: {:?}", result.url, e);
+                    let short_err = shorten_error(&e, 40);
+                    pb_clone_for_post_download.set_style(error_style_clone);
+                    pb_clone_for_post_download.finish_with_message(short_err);
+                    result.status = if e.downcast_ref::<FileTimeoutError>().is_some() {
+                        DownloadStatus::TimedOut
+                    } else {
+                        DownloadStatus::Failed
+                    };
+                    result.error = Some(format!("{:#}", e));
+                    result.failure = Some(classify_failure(&e));
+                }
             }
-        })
+            if continue_on_disk_full
+                && result.status == DownloadStatus::Failed
+                && result.failure == Some(FailureKind::DiskFull)
+                && !disk_full.swap(true, Ordering::Relaxed)
+            {
+                print_message("[WARN] The disk is full: letting running downloads finish, not starting new ones.");
+            }
+            result.finished_at = Utc::now();
+            result.duration_secs = started.elapsed().as_secs_f64();
+            result.avg_bytes_per_sec = average_rate(result.bytes, result.duration_secs);
+            tracker.finish(task_id, result.status.is_failure());
+            if jsonl {
+                emit_json_line(&result);
+            }
+            result
+        });
+        abort_handles.lock().unwrap().push(handle.abort_handle());
+        handle
     });
     
-    let stream = futures_util::stream::iter(download_futs);
-    // Use the user-provided concurrency for the actual downloads.
-    stream.buffer_unordered(concurrency).for_each(|_| async {}).await;
+    let mut stream = futures_util::stream::iter(download_futs)
+        // Use the user-provided concurrency for the actual downloads.
+        .buffer_unordered(concurrency);
+    let mut results: Vec<DownloadResult> = Vec::new();
+    let mut fatal_failure = None;
+    while let Some(joined) = stream.next().await {
+        let result = match joined {
+            Ok(result) => result,
+            Err(e) => {
+                error!("Download task panicked: {}", e);
+                continue;
+            }
+        };
+        if options.fail_fast && result.status.is_failure() {
+            fatal_failure = Some((result.url.clone(), result.error.clone().unwrap_or_default()));
+            break;
+        }
+        results.push(result);
+    }
+    drop(stream);
+    drop(keyboard);
+    if fatal_failure.is_some() {
+        for handle in abort_handles.lock().unwrap().iter() {
+            handle.abort();
+        }
+        multi_progress.clear().ok();
+    }
     
     overall_pb.finish_with_message("All downloads finished.");
+    eta_monitor.abort();
+    if let Some((handle, line)) = compact_line {
+        handle.abort();
+        line.finish_and_clear();
+    }
+    // Fold in the last partial interval so short runs still report a peak.
+    tracker.sample_throughput();
+    let run_duration = run_started.elapsed();
+    if let Some(handle) = text_progress {
+        handle.abort();
+        eprintln!("{}", format_progress_line(&tracker));
+    }
+    if let (Some(handle), Some(path)) = (status_writer, &options.status_file) {
+        handle.abort();
+        write_status_file(path, &StatusSnapshot::capture(&tracker, true)).await?;
+    }
+    
+    if let Some((url, error)) = fatal_failure {
+        return Err(anyhow!(
+            "Stopping after the first failure (--fail-fast): {}: {}",
+            url,
+            error
+        ));
+    }
     
     eprintln!("\nAll downloads processed.");
+    let summary = RunSummary::from_results(&results, run_duration, tracker.peak_bytes_per_sec());
+    print_summary(&results, &summary);
+    if options.strict_verify {
+        print_checksum_sources(&results);
+    }
+    if options.jsonl {
+        emit_json_line(&SummaryLine { summary: &summary });
+    }
+    let failures = summary.failed + summary.timed_out + summary.readback_failed + summary.not_started;
+    if failures > 0 {
+        return Err(anyhow!("{} of {} file(s) could not be downloaded.", failures, summary.files));
+    }
     Ok(())
 }
 
-async fn download_file(task: DownloadTask) -> Result<()> {
-    let url = &task.item.url;
+/// Re-opens a finished file and reads it back in full, so corruption introduced by the storage
+/// layer after a successful write is caught rather than trusted.
+async fn verify_readback(path: &Path, expected_size: Option<u64>, expected_sha256: Option<&str>) -> Result<()> {
+    let size = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Failed to stat {}", path.display()))?
+        .len();
+    if let Some(expected) = expected_size.filter(|&expected| expected != size) {
+        return Err(anyhow!(
+            "size on disk is {} bytes, expected {}",
+            size,
+            expected
+        ));
+    }
+    // Hashing reads every byte, which is what surfaces read errors even without an expected digest.
+    let digest = sha256_file_async(path).await?;
+    match expected_sha256 {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest) => Err(anyhow!(
+            "SHA-256 on disk is {}, expected {}",
+            digest,
+            expected
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn overall_bar_style() -> ProgressStyle {
+    // The Fix: Overall progress bar template now matches individual bars for consistency and custom formatting.
+    ProgressStyle::with_template(
+        "Overall Progress: [{bar:40.yellow/blue}] {percent:>3}% │ {bytes_formatted}/{total_bytes_formatted} @ {bytes_per_sec} │ ETA: {eta_formatted}"
+    ).expect("Invalid overall progress bar template")
+     .with_key("bytes_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_bytes(state.pos())).unwrap())
+     .with_key("total_bytes_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_bytes(state.len().unwrap_or(0))).unwrap())
+     .with_key("eta_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_duration_human(state.eta(), false)).unwrap())
+     .progress_chars("=> ")
+}
+
+/// Overall bar without a total: a spinner and a byte counter, used while sizes are unknown.
+fn overall_spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "Overall Progress: {spinner:.yellow} {bytes_formatted} @ {bytes_per_sec} │ {elapsed_precise} (total size not known yet)"
+    ).expect("Invalid overall spinner template")
+     .with_key("bytes_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_bytes(state.pos())).unwrap())
+}
+
+/// Adds a size learned from a response to the overall total, switching the overall bar back
+/// from the spinner once every size is known.
+fn add_learned_size(task: &DownloadTask, size: u64) {
+    if task.tracker.add_learned_size(size) {
+        let overall_pb = &task.overall_progress_bar;
+        overall_pb.disable_steady_tick();
+        overall_pb.set_style(overall_bar_style());
+    }
+}
+
+/// Keeps the N largest or smallest items, in their original order. Files whose size is
+/// unknown can't be ranked and are left out.
+fn select_top_files(items: Vec<DownloadItem>, sizes: &HashMap<String, u64>, top: TopFiles) -> Vec<DownloadItem> {
+    let (count, largest) = match top {
+        TopFiles::Largest(n) => (n, true),
+        TopFiles::Smallest(n) => (n, false),
+    };
+    let mut ranked: Vec<(usize, u64)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| sizes.get(&item.url).map(|&size| (i, size)))
+        .collect();
+    let unknown = items.len() - ranked.len();
+    if unknown > 0 {
+        print_message(format!(
+            "[WARN] Leaving out {} file(s) of unknown size from the size ranking.",
+            unknown
+        ));
+    }
+    if largest {
+        ranked.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+    } else {
+        ranked.sort_by_key(|&(_, size)| size);
+    }
+    let keep: HashSet<usize> = ranked.into_iter().take(count).map(|(i, _)| i).collect();
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| keep.contains(i))
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Keeps files in list order while their summed size stays within `cap`, stopping at the first
+/// file that would exceed it. Files of unknown size can't be counted and are left out.
+fn select_within_size(items: Vec<DownloadItem>, sizes: &HashMap<String, u64>, cap: u64) -> Vec<DownloadItem> {
+    let mut selected = Vec::new();
+    let mut selected_size = 0;
+    let mut excluded = Vec::new();
+    let mut excluded_size = 0;
+    let mut unknown = 0;
+    let mut full = false;
+    for item in items {
+        match sizes.get(&item.url) {
+            None => unknown += 1,
+            Some(&size) if !full && selected_size + size <= cap => {
+                selected_size += size;
+                selected.push(item);
+            }
+            Some(&size) => {
+                full = true;
+                excluded_size += size;
+                excluded.push(item.source_name());
+            }
+        }
+    }
+    if unknown > 0 {
+        print_message(format!(
+            "[WARN] Leaving out {} file(s) of unknown size, which --max-total-size can't account for.",
+            unknown
+        ));
+    }
+    print_message(format!(
+        "[INFO] --max-total-size {}: selected {} file(s), {} in total.",
+        format_bytes(cap),
+        selected.len(),
+        format_bytes(selected_size)
+    ));
+    if !excluded.is_empty() {
+        let count = excluded.len();
+        let more = match count.saturating_sub(MAX_LISTED_EXCLUDED) {
+            0 => String::new(),
+            n => format!(" and {} more", n),
+        };
+        excluded.truncate(MAX_LISTED_EXCLUDED);
+        print_message(format!(
+            "[INFO] Left out {} file(s), {}: {}{}",
+            count,
+            format_bytes(excluded_size),
+            excluded.join(", "),
+            more
+        ));
+    }
+    selected
+}
+
+/// Lists each planned download with its size and whether a SHA-256 is known to verify it against.
+fn print_dry_run(items: &[DownloadItem], base_dir: &Path, sizes: &HashMap<String, u64>, to_stdout: bool) {
+    let mut total_size = 0;
+    let mut with_checksum = 0;
+    for item in items {
+        let size = sizes.get(&item.url).copied().unwrap_or(0);
+        total_size += size;
+        let checksum = if item.sha256.is_some() {
+            with_checksum += 1;
+            "sha256"
+        } else {
+            "none"
+        };
+        let destination = if to_stdout {
+            PathBuf::from(STDOUT_DESTINATION)
+        } else {
+            base_dir.join(generate_actual_filename(&item.url, item.preferred_filename.as_deref()))
+        };
+        println!("{:>10}  {:<6}  {}", format_bytes(size), checksum, destination.display());
+    }
+    eprintln!(
+        "[INFO] Dry run: {} file(s), {} in total. Checksums available for {} of {} file(s); nothing was downloaded.",
+        items.len(),
+        format_bytes(total_size),
+        with_checksum,
+        items.len()
+    );
+}
+
+/// True for 404 Not Found and 410 Gone, the statuses `--skip-missing` treats as a skip.
+fn is_missing(err: &anyhow::Error) -> bool {
+    matches!(
+        http_status(err),
+        Some(reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE)
+    )
+}
+
+/// Prints per-status counts and throughput, listing timed-out files separately from other failures.
+fn print_summary(results: &[DownloadResult], summary: &RunSummary) {
+    let mut extra = String::new();
+    if summary.missing > 0 {
+        extra.push_str(&format!(", {} missing", summary.missing));
+    }
+    if summary.cancelled > 0 {
+        extra.push_str(&format!(", {} cancelled", summary.cancelled));
+    }
+    if summary.readback_failed > 0 {
+        extra.push_str(&format!(", {} failed readback", summary.readback_failed));
+    }
+    if summary.not_started > 0 {
+        extra.push_str(&format!(", {} not started (disk full)", summary.not_started));
+    }
+    if summary.checksum_recovered > 0 {
+        extra.push_str(&format!(", {} fixed by re-downloading", summary.checksum_recovered));
+    }
+    eprintln!(
+        "Summary: {} downloaded, {} skipped, {} linked, {} failed, {} timed out{}.",
+        summary.completed, summary.skipped, summary.linked, summary.failed, summary.timed_out, extra
+    );
+    let peak = match summary.peak_bytes_per_sec {
+        Some(peak) => format!(", peak {}/s", format_bytes(peak as u64)),
+        None => String::new(),
+    };
+    eprintln!(
+        "Transferred {} in {} (average {}/s{}).",
+        format_bytes(summary.bytes),
+        format_duration_human(Duration::from_secs_f64(summary.duration_secs), true),
+        format_bytes(summary.avg_bytes_per_sec as u64),
+        peak
+    );
+    for result in results.iter().filter(|r| r.status == DownloadStatus::TimedOut) {
+        eprintln!(
+            "[WARN] Timed out after {} attempt(s): {}",
+            result.attempts,
+            result.path.display()
+        );
+    }
+    for result in results.iter().filter(|r| r.status == DownloadStatus::Missing) {
+        eprintln!("[INFO] Missing on the server: {}", result.url);
+    }
+    for result in results.iter().filter(|r| r.status == DownloadStatus::Cancelled) {
+        eprintln!("[INFO] Cancelled from the keyboard: {}", result.path.display());
+    }
+    for result in results.iter().filter(|r| r.status == DownloadStatus::NotStarted) {
+        eprintln!("[WARN] Not started because the disk is full: {}", result.path.display());
+    }
+    for result in results.iter().filter(|r| r.status == DownloadStatus::ReadbackFailed) {
+        eprintln!(
+            "[WARN] Readback check failed for {}: {}",
+            result.path.display(),
+            result.error.as_deref().unwrap_or_default()
+        );
+    }
+    for result in results.iter().filter(|r| r.checksum_redownloads > 0 && !r.status.is_failure()) {
+        eprintln!(
+            "[INFO] Checksum matched after {} re-download(s), so the earlier copy was corrupted in transfer: {}",
+            result.checksum_redownloads,
+            result.path.display()
+        );
+    }
+    for result in results.iter().filter(|r| r.status == DownloadStatus::Failed) {
+        let kind = result.failure.unwrap_or(FailureKind::Other);
+        eprintln!(
+            "[WARN] Failed ({}) after {} attempt(s): {}",
+            kind.label(),
+            result.attempts,
+            result.url
+        );
+        if kind == FailureKind::Checksum && result.checksum_redownloads > 0 {
+            eprintln!(
+                "[WARN]   The checksum still didn't match after {} re-download(s); the remote file likely differs from the declared checksum.",
+                result.checksum_redownloads
+            );
+        }
+    }
+}
+
+/// Shows how many files each checksum source covered, and which files had none.
+fn print_checksum_sources(results: &[DownloadResult]) {
+    let mut counts: Vec<(ChecksumSource, usize)> = Vec::new();
+    for source in results.iter().filter_map(|r| r.checksum_source) {
+        match counts.iter_mut().find(|(s, _)| *s == source) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((source, 1)),
+        }
+    }
+    let unverifiable: Vec<&DownloadResult> = results.iter().filter(|r| r.checksum_source.is_none()).collect();
+    let mut parts: Vec<String> = counts
+        .iter()
+        .map(|(source, count)| format!("{} from {}", count, source.label()))
+        .collect();
+    parts.push(format!("{} unavailable", unverifiable.len()));
+    eprintln!("Checksums: {}.", parts.join(", "));
+    for result in unverifiable {
+        eprintln!("[WARN] No checksum available: {}", result.path.display());
+    }
+}
+
+/// Asks whether to go ahead with a large download on a metered connection.
+fn confirm_metered_download(total_size: u64) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Refusing to download {} on a metered connection without confirmation (stdin is not a terminal).",
+            format_bytes(total_size)
+        ));
+    }
+    eprint!(
+        "This run will download {} over a metered connection. Continue? [y/N]: ",
+        format_bytes(total_size)
+    );
+    std::io::stderr().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Writes a value as a single JSON line to stdout, keeping stderr free for progress output.
+fn emit_json_line(value: &impl Serialize) {
+    match serde_json::to_string(value) {
+        Ok(line) => {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", line);
+            let _ = stdout.flush();
+        }
+        Err(e) => error!("Failed to serialize JSON line: {}", e),
+    }
+}
+
+/// What `download_verified` did, across every re-download.
+struct VerifiedDownload {
+    outcome: Result<DownloadOutcome>,
+    attempts: u32,
+    /// Re-downloads caused by checksum mismatches.
+    redownloads: u32,
+    /// Bytes transferred, counting each re-download and kept even when verification fails.
+    bytes: u64,
+}
+
+/// `download_with_retries` followed by the checksum check of `--strict-verify` and
+/// `--checksums-url`. On a mismatch the file is deleted and downloaded again, up to
+/// `--retry-on-checksum-mismatch` times.
+async fn download_verified(task: &DownloadTask) -> VerifiedDownload {
+    // Add progress bar to display now that this download is starting; --compact keeps it hidden
+    // and only reads its position for the activity line.
+    let pb = if task.options.compact {
+        task.progress_bar.clone()
+    } else {
+        task.multi_progress.add(task.progress_bar.clone())
+    };
+    let mut attempts = 0;
+    let mut redownloads = 0;
+    let mut bytes = 0;
+    loop {
+        let (outcome, used) = download_with_retries(task, &pb).await;
+        attempts += used;
+        if let Ok(DownloadOutcome::Downloaded { bytes: transferred }) = &outcome {
+            bytes += transferred;
+        }
+        // What a completed transfer added to the overall bar; failed attempts roll theirs back.
+        let counted = match &outcome {
+            Ok(DownloadOutcome::Downloaded { .. }) => pb.position(),
+            _ => 0,
+        };
+        let outcome = match outcome {
+            Ok(outcome) => verify_outcome(task, outcome).await,
+            Err(e) => Err(e),
+        };
+        let mismatched_path = outcome.as_ref().err().and_then(|e| {
+            e.chain()
+                .find_map(|cause| cause.downcast_ref::<ChecksumMismatchError>())
+                .map(|mismatch| mismatch.path.clone())
+        });
+        // Never delete a copy found through --exclude-existing-in; only this run's own file.
+        let retry = redownloads < task.options.checksum_retries
+            && mismatched_path.as_deref() == Some(task.destination_path.as_path());
+        if !retry {
+            return VerifiedDownload { outcome, attempts, redownloads, bytes };
+        }
+        redownloads += 1;
+        // verify_outcome has already deleted the mismatching file.
+        print_message(format!(
+            "[WARN] Checksum mismatch for {}; downloading it again ({}/{}).",
+            task.destination_path.display(),
+            redownloads,
+            task.options.checksum_retries
+        ));
+        task.overall_progress_bar.dec(counted);
+        pb.set_position(0);
+    }
+}
+
+/// The digest a file must be checked against: always for checksums the user supplied (a
+/// `--manifest` entry or `--checksums-url`), and for any declared checksum with `--strict-verify`.
+fn expected_sha256(item: &DownloadItem, strict_verify: bool) -> Option<&str> {
+    let declared_by_user = matches!(
+        item.checksum_source,
+        Some(ChecksumSource::Manifest | ChecksumSource::ChecksumsFile)
+    );
+    item.sha256.as_deref().filter(|_| strict_verify || declared_by_user)
+}
+
+/// Checks the SHA-256 of the file an outcome left in place, when `expected_sha256` asks for it.
+/// Linked files are skipped: the store is addressed by hash.
+async fn verify_outcome(task: &DownloadTask, outcome: DownloadOutcome) -> Result<DownloadOutcome> {
+    let Some(sha256) = expected_sha256(&task.item, task.options.strict_verify) else {
+        return Ok(outcome);
+    };
+    if task.options.to_stdout {
+        // Streamed bytes are gone; -O - refuses the options that require verification.
+        debug!("Not verifying {}: it was streamed to stdout", task.item.url);
+        return Ok(outcome);
+    }
+    let (path, own_file) = match &outcome {
+        DownloadOutcome::Downloaded { .. } | DownloadOutcome::AlreadyComplete => (&task.destination_path, true),
+        DownloadOutcome::FoundElsewhere { path, linked: false } => (path, false),
+        _ => return Ok(outcome),
+    };
+    verify_file(path, sha256, own_file).await?;
+    Ok(outcome)
+}
+
+/// Verifies `path` against `sha256`. A mismatching file this run owns is deleted, so a later run
+/// downloads it again instead of taking it for complete.
+async fn verify_file(path: &Path, sha256: &str, own_file: bool) -> Result<()> {
+    let Err(e) = verify_sha256(path, sha256).await else {
+        return Ok(());
+    };
+    error!("Verification failed for {}: {:#}", path.display(), e);
+    if own_file && e.downcast_ref::<ChecksumMismatchError>().is_some() {
+        if let Err(remove_err) = tokio::fs::remove_file(path).await {
+            error!("Failed to remove mismatching file {}: {}", path.display(), remove_err);
+        }
+    }
+    Err(e)
+}
+
+/// Runs `download_file` until it succeeds or the retry budget is spent.
+/// Returns the final outcome and the number of attempts made.
+async fn download_with_retries(task: &DownloadTask, pb: &ProgressBar) -> (Result<DownloadOutcome>, u32) {
+    let remote_size = task.progress_bar.length().unwrap_or(0);
+    let smaller_than_remote = task.options.overwrite_if_smaller
+        && !task.options.to_stdout
+        && remote_size > 0
+        && tokio::fs::metadata(&task.destination_path)
+            .await
+            .is_ok_and(|m| m.len() < remote_size);
+    if (task.force || smaller_than_remote) && task.destination_path.exists() {
+        // Done once up front, so retries still resume whatever this run downloads.
+        if task.force {
+            info!("Forcing re-download of {}", task.destination_path.display());
+        } else {
+            info!(
+                "Replacing {}: it is smaller than the remote file ({} bytes)",
+                task.destination_path.display(),
+                remote_size
+            );
+        }
+        if let Err(e) = tokio::fs::remove_file(&task.destination_path).await {
+            let err = anyhow::Error::new(e)
+                .context(format!("Failed to remove {}", task.destination_path.display()));
+            return (Err(err), 0);
+        }
+    }
+    // Bytes already written to stdout can't be taken back, so a streamed download gets one attempt.
+    // Every mirror gets at least one attempt, even when retries are exhausted sooner.
+    let sources = 1 + task.item.mirrors.len() as u32;
+    let max_attempts = if task.options.to_stdout { 1 } else { (task.options.retries + 1).max(sources) };
+    let mut attempt = 1;
+    loop {
+        let url = task.item.url_for_attempt(attempt);
+        if url != task.item.url {
+            info!("Trying mirror {} for {}", url, task.item.url);
+        }
+        let outcome = download_file(task, pb, url).await;
+        let err = match outcome {
+            Ok(outcome) => return (Ok(outcome), attempt),
+            Err(e) => e,
+        };
+        if err.downcast_ref::<SkippedByUserError>().is_some() {
+            return (Err(err), attempt);
+        }
+        let kind = classify_failure(&err);
+        // A mirror that hasn't been tried yet may work even when this error wouldn't clear on retry.
+        let untried_mirror = attempt < sources;
+        if attempt >= max_attempts || !(untried_mirror || is_retryable(&err, kind, &task.options)) {
+            return (Err(err), attempt);
+        }
+        let delay = if untried_mirror { Duration::ZERO } else { retry_delay(attempt, kind) };
+        log::warn!(
+            "Attempt {}/{} for {} failed ({}): {:#}. Retrying in {:.1}s",
+            attempt, max_attempts, task.item.url, kind.label(), err, delay.as_secs_f64()
+        );
+        pb.set_message(format!("{} [Retry {}]", truncate_filename(&task.destination_path.to_string_lossy(), 20), attempt));
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// HTTP client errors (4xx), HTML error pages and encoded range responses won't change on retry;
+/// DNS and connection-refused errors are retried unless disabled; everything else might be transient.
+fn is_retryable(err: &anyhow::Error, kind: FailureKind, options: &DownloadOptions) -> bool {
+    if err.downcast_ref::<UnexpectedHtmlError>().is_some()
+        || err.downcast_ref::<EncodedRangeError>().is_some()
+    {
+        return false;
+    }
+    match kind {
+        FailureKind::HttpClientError => false,
+        FailureKind::Dns | FailureKind::ConnectionRefused => options.retry_connect_errors,
+        // Retrying won't free any space.
+        FailureKind::Checksum | FailureKind::DiskFull => false,
+        FailureKind::HttpServerError | FailureKind::Other => true,
+    }
+}
+
+/// Exponential backoff with random jitter of up to half the interval, so downloads that
+/// failed together don't all retry at the same moment. Connect errors use a shorter schedule.
+fn retry_delay(attempt: u32, kind: FailureKind) -> Duration {
+    let (base, max) = if kind.is_connect_error() {
+        (CONNECT_RETRY_BASE_DELAY, CONNECT_RETRY_MAX_DELAY)
+    } else {
+        (RETRY_BASE_DELAY, RETRY_MAX_DELAY)
+    };
+    let backoff = base
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(max);
+    backoff + backoff.mul_f64(fastrand::f64() * 0.5)
+}
+
+async fn download_file(task: &DownloadTask, pb: &ProgressBar, url: &str) -> Result<DownloadOutcome> {
     let path = &task.destination_path;
     let overall_pb = &task.overall_progress_bar;
     let client = &task.client;
     
-    // Add progress bar to display now that this download is starting
-    let pb = task.multi_progress.add(task.progress_bar);
-    
     info!("Starting download for URL: {}", url);
     debug!("Destination path: {}", path.display());
     
-    let result = (async {
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                tokio::fs::create_dir_all(parent).await
-                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    let cas_dir = task.options.cas_dir.as_deref();
+    let expected_sha256 = task.item.sha256.as_deref();
+    if let (Some(cas_dir), Some(sha256)) = (cas_dir, expected_sha256) {
+        if cas::link_if_stored(cas_dir, sha256, path).await? {
+            info!("Linked {} from the content-addressed store", path.display());
+            overall_pb.inc(pb.length().unwrap_or(0));
+            return Ok(DownloadOutcome::LinkedFromStore);
+        }
+    }
+
+    if !task.force && !task.existing_candidates.is_empty() && !path.exists() {
+        if let Some(existing) = find_existing_copy(task, pb.length().filter(|&l| l > 0)).await? {
+            let linked = task.options.link_existing;
+            if linked {
+                cas::replace_with_link(&existing, path).await?;
+                info!("Linked {} from {}", path.display(), existing.display());
+            } else {
+                info!("Not downloading {}: {} already has it", url, existing.display());
             }
+            overall_pb.inc(pb.length().unwrap_or(0));
+            return Ok(DownloadOutcome::FoundElsewhere { path: existing, linked });
+        }
+    }
+
+    // Bytes this attempt added to the overall bar, rolled back if it fails so a retry doesn't double count.
+    let mut overall_added = 0;
+    let transfer = async {
+        if task.options.to_stdout {
+            return stream_to_stdout(task, pb, url, &mut overall_added).await;
         }
 
         let mut current_size = 0;
@@ -207,31 +1589,110 @@ async fn download_file(task: DownloadTask) -> Result<()> {
             current_size = tokio::fs::metadata(path).await?.len();
         }
         
-        let total_size = pb.length().unwrap_or(0);
+        let mut total_size = pb.length().unwrap_or(0);
         if total_size > 0 && current_size >= total_size {
             debug!("File {} already complete.", path.display());
             pb.set_position(total_size);
             overall_pb.inc(total_size.saturating_sub(current_size));
+            overall_added += total_size.saturating_sub(current_size);
             // The Fix: Set message for finished state here.
             pb.set_message(format!("{} [Done]", truncate_filename(&path.to_string_lossy(), 20)));
-            return Ok(());
+            return Ok(DownloadOutcome::AlreadyComplete);
         }
         
-        let mut request = client.get(url);
+        let parts = task.connection_count();
+        if current_size == 0 && parts > 1 {
+            let split_added = AtomicU64::new(0);
+            let split_result = download_split(task, pb, url, total_size, parts, &split_added).await;
+            overall_added += split_added.load(Ordering::Relaxed);
+            match split_result {
+                Ok(()) => {
+                    pb.set_message(format!("{} [Done]", truncate_filename(&path.to_string_lossy(), 20)));
+                    info!("Finished split download for {}", url);
+                    return Ok(DownloadOutcome::Downloaded { bytes: total_size });
+                }
+                Err(e) if e.downcast_ref::<RangeNotSupportedError>().is_some() => {
+                    debug!("{} does not support ranges, falling back to a single stream", url);
+                    overall_pb.dec(split_added.load(Ordering::Relaxed));
+                    overall_added -= split_added.load(Ordering::Relaxed);
+                    pb.set_position(0);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Ask for the raw bytes: a compressed body would break resume offsets and the saved file.
+        let mut request = client
+            .get(url)
+            .headers(task.headers_for(url))
+            .header(reqwest::header::ACCEPT_ENCODING, "identity");
         if current_size > 0 {
             debug!("Resuming download for {} from byte {}", path.display(), current_size);
             request = request.header(reqwest::header::RANGE, format!("bytes={}-", current_size));
         }
         
-        let resp = request.send().await?.error_for_status()?;
+        let mut resp = request.send().await?.error_for_status()?;
+
+        if !task.options.allow_html && is_unexpected_html(&resp, path) {
+            let first_chunk = resp.chunk().await.ok().flatten().unwrap_or_default();
+            let preview_len = first_chunk.len().min(HTML_PREVIEW_LEN);
+            let preview = String::from_utf8_lossy(&first_chunk[..preview_len]).to_string();
+            debug!("HTML response for {} starts with: {}", url, preview);
+            return Err(UnexpectedHtmlError { url: url.to_string(), preview }.into());
+        }
 
         let is_resume = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let content_encoding = resp
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && !v.eq_ignore_ascii_case("identity"))
+            .map(str::to_string);
+        if let Some(encoding) = &content_encoding {
+            if is_resume {
+                // Appending encoded bytes at a decoded offset would silently corrupt the file.
+                return Err(EncodedRangeError { url: url.to_string(), encoding: encoding.clone() }.into());
+            }
+            log::warn!(
+                "{} was served with Content-Encoding '{}' despite requesting identity; the file is saved as received",
+                url, encoding
+            );
+        }
         if !is_resume && current_size > 0 {
-            eprintln!("[WARN] Server does not support resume for {}. Starting from beginning.", url);
+            print_message(format!("[WARN] Server does not support resume for {}. Starting from beginning.", url));
             overall_pb.inc(0_u64.saturating_sub(current_size));
             current_size = 0;
         } else {
             overall_pb.inc(current_size);
+            overall_added += current_size;
+        }
+
+        // The prescan may have failed to learn the size; the actual response can still declare it.
+        if total_size == 0 {
+            if let Some(len) = resp.content_length().filter(|&l| l > 0) {
+                total_size = current_size + len;
+                debug!("Learned size {} for {} from the download response", total_size, url);
+                pb.set_length(total_size);
+                add_learned_size(task, total_size);
+            }
+        }
+        let length_unknown = total_size == 0;
+        if length_unknown {
+            let is_chunked = resp
+                .headers()
+                .get(reqwest::header::TRANSFER_ENCODING)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+            if is_chunked {
+                debug!("{} uses chunked transfer without a declared length", url);
+            } else {
+                log::warn!(
+                    "{} declares neither Content-Length nor chunked encoding; truncation cannot be detected",
+                    url
+                );
+            }
+            pb.set_style(indeterminate_style());
         }
 
         let mut file = if is_resume {
@@ -242,46 +1703,228 @@ async fn download_file(task: DownloadTask) -> Result<()> {
 
         pb.set_position(current_size);
 
+        let mut bytes_transferred = 0;
+        // Servers often yield many small chunks; coalescing them cuts write syscalls and bar redraws.
+        let chunk_size = task.options.chunk_size.max(1);
+        let mut buffer: Vec<u8> = Vec::with_capacity(chunk_size);
         let mut stream = resp.bytes_stream();
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result.context("Failed to read chunk from download stream")?;
-            file.write_all(&chunk).await.context("Failed to write chunk to file")?;
-            let chunk_len = chunk.len() as u64;
-            pb.inc(chunk_len);
-            overall_pb.inc(chunk_len);
+        loop {
+            task.control.checkpoint().await?;
+            let next = stream.next().await;
+            let chunk = match next {
+                Some(Ok(chunk)) => Some(chunk),
+                Some(Err(e)) => {
+                    // Keep what was already received so a retry can resume from it.
+                    if !buffer.is_empty() {
+                        file.write_all(&buffer).await.context("Failed to write chunk to file")?;
+                        let buffered = buffer.len() as u64;
+                        bytes_transferred += buffered;
+                        task.tracker.record_transferred(buffered);
+                        pb.inc(buffered);
+                        overall_pb.inc(buffered);
+                        overall_added += buffered;
+                    }
+                    // A chunked body that ends without its terminating chunk surfaces here as an error,
+                    // which is the only signal of truncation when no length was declared.
+                    let context = if length_unknown {
+                        format!("Stream for {} ended abnormally after {} bytes", url, bytes_transferred)
+                    } else {
+                        "Failed to read chunk from download stream".to_string()
+                    };
+                    return Err(anyhow::Error::new(e).context(context));
+                }
+                None => None,
+            };
+            let finished = chunk.is_none();
+            if let Some(chunk) = chunk {
+                buffer.extend_from_slice(&chunk);
+            }
+            if buffer.len() >= chunk_size || (finished && !buffer.is_empty()) {
+                file.write_all(&buffer).await.context("Failed to write chunk to file")?;
+                let written = buffer.len() as u64;
+                buffer.clear();
+                bytes_transferred += written;
+                task.tracker.record_transferred(written);
+                pb.inc(written);
+                overall_pb.inc(written);
+                overall_added += written;
+            }
+            if finished {
+                break;
+            }
         }
         
+        file.flush().await.context("Failed to flush file")?;
         let final_len = tokio::fs::metadata(path).await?.len();
+        if length_unknown {
+            info!(
+                "Received {} bytes for {} without a declared length; final file size is {}",
+                bytes_transferred, url, final_len
+            );
+            if final_len != current_size + bytes_transferred {
+                return Err(anyhow!(
+                    "File size mismatch for {}: wrote {} bytes but file is {} bytes",
+                    url,
+                    current_size + bytes_transferred,
+                    final_len
+                ));
+            }
+        }
         if total_size > 0 && final_len < total_size {
-            eprintln!("[WARN] Download for {} may be incomplete. Expected {}, got {}.", url, total_size, final_len);
+            print_message(format!("[WARN] Download for {} may be incomplete. Expected {}, got {}.", url, total_size, final_len));
             return Err(anyhow!("Incomplete download for {}", url));
         }
 
         // The Fix: Set message for finished state here.
         pb.set_message(format!("{} [Done]", truncate_filename(&path.to_string_lossy(), 20)));
         info!("Finished download for {}", url);
-        Ok(())
-    }).await;
+        Ok(DownloadOutcome::Downloaded { bytes: bytes_transferred })
+    };
+    // The timeout drops the transfer in place, so the rollback below still sees what it added.
+    let result = match task.options.timeout_per_file {
+        Some(limit) => match tokio::time::timeout(limit, transfer).await {
+            Ok(result) => result,
+            Err(_) => Err(FileTimeoutError { url: url.to_string(), limit }.into()),
+        },
+        None => transfer.await,
+    };
 
-    if let Err(e) = result {
-        // Error handling for progress bar is now done in the parent `run_downloads` loop.
-        return Err(e);
+    if result.is_err() {
+        overall_pb.dec(overall_added);
     }
-    
-    Ok(())
+
+    // Error handling for progress bar is now done in the parent `run_downloads` loop.
+    let outcome = result?;
+
+    if let Some(cas_dir) = cas_dir {
+        pb.set_message(format!("{} [Storing]", truncate_filename(&path.to_string_lossy(), 20)));
+        let sha256 = cas::ingest(cas_dir, path, expected_sha256).await?;
+        debug!("{} is stored as {}", path.display(), sha256);
+    }
+
+    Ok(outcome)
+}
+
+/// The first `--exclude-existing-in` candidate that is a file of the expected size and, when the
+/// source declares one, the expected SHA-256.
+async fn find_existing_copy(task: &DownloadTask, expected_size: Option<u64>) -> Result<Option<PathBuf>> {
+    for candidate in &task.existing_candidates {
+        let Ok(metadata) = tokio::fs::metadata(candidate).await else {
+            continue;
+        };
+        if !metadata.is_file() || expected_size.is_some_and(|size| size != metadata.len()) {
+            debug!("{} exists but doesn't match the remote size", candidate.display());
+            continue;
+        }
+        if let Some(expected) = task.item.sha256.as_deref() {
+            if !expected.eq_ignore_ascii_case(&sha256_file_async(candidate).await?) {
+                debug!("{} exists but doesn't match the expected SHA-256", candidate.display());
+                continue;
+            }
+        }
+        return Ok(Some(candidate.clone()));
+    }
+    Ok(None)
+}
+
+/// Streams the response body to stdout. There is no resume or "already complete" skip,
+/// since nothing on disk describes what the reader has received.
+async fn stream_to_stdout(task: &DownloadTask, pb: &ProgressBar, url: &str, added: &mut u64) -> Result<DownloadOutcome> {
+    let resp = task
+        .client
+        .get(url)
+        .headers(task.headers_for(url))
+        .header(reqwest::header::ACCEPT_ENCODING, "identity")
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // Judge HTML responses by the name the file would have had on disk.
+    let name = generate_actual_filename(url, task.item.preferred_filename.as_deref());
+    if !task.options.allow_html && is_unexpected_html(&resp, Path::new(&name)) {
+        return Err(UnexpectedHtmlError { url: url.to_string(), preview: String::new() }.into());
+    }
+
+    let mut total_size = pb.length().unwrap_or(0);
+    if total_size == 0 {
+        if let Some(len) = resp.content_length().filter(|&l| l > 0) {
+            total_size = len;
+            pb.set_length(total_size);
+            add_learned_size(task, total_size);
+        }
+    }
+    if total_size == 0 {
+        pb.set_style(indeterminate_style());
+    }
+
+    let mut stdout = tokio::io::stdout();
+    let chunk_size = task.options.chunk_size.max(1);
+    let mut buffer: Vec<u8> = Vec::with_capacity(chunk_size);
+    let mut bytes_transferred = 0;
+    let mut stream = resp.bytes_stream();
+    loop {
+        task.control.checkpoint().await?;
+        let chunk = stream
+            .next()
+            .await
+            .transpose()
+            .context("Failed to read chunk from download stream")?;
+        let finished = chunk.is_none();
+        if let Some(chunk) = chunk {
+            buffer.extend_from_slice(&chunk);
+        }
+        if buffer.len() >= chunk_size || (finished && !buffer.is_empty()) {
+            stdout.write_all(&buffer).await.context("Failed to write to stdout")?;
+            let written = buffer.len() as u64;
+            buffer.clear();
+            bytes_transferred += written;
+            task.tracker.record_transferred(written);
+            pb.inc(written);
+            task.overall_progress_bar.inc(written);
+            *added += written;
+        }
+        if finished {
+            break;
+        }
+    }
+    stdout.flush().await.context("Failed to flush stdout")?;
+
+    if total_size > 0 && bytes_transferred < total_size {
+        return Err(anyhow!(
+            "Incomplete download for {}: expected {} bytes, streamed {}",
+            url, total_size, bytes_transferred
+        ));
+    }
+    info!("Finished streaming {} to stdout", url);
+    Ok(DownloadOutcome::Downloaded { bytes: bytes_transferred })
 }
 
+/// Logs when a size declared by the source disagrees with what the server reports.
+fn log_size_discrepancy(name: &str, declared: Option<u64>, observed: u64) {
+    if let Some(declared) = declared.filter(|&d| d != observed) {
+        debug!(
+            "Size mismatch for {}: source declared {} bytes, server reports {} bytes",
+            name, declared, observed
+        );
+    }
+}
 
 /// Fetches the size of a remote file using a robust, two-stage approach.
-async fn fetch_file_size(client: &reqwest::Client, url: &str) -> Result<u64> {
+async fn fetch_file_size(client: &reqwest::Client, url: &str, headers: &HeaderMap) -> Result<u64> {
     debug!("Fetching size for URL: {}", url);
 
     // Stage 1: Attempt HEAD request. The client is configured to follow redirects automatically.
-    let head_resp = client.head(url).send().await;
+    let head_resp = client.head(url).headers(headers.clone()).send().await;
 
     if let Ok(resp) = head_resp {
         if resp.status().is_success() {
-            if let Some(length) = resp.content_length() {
+            // A HEAD response has no body, so read the declared length from the header itself.
+            let declared = resp
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok());
+            if let Some(length) = declared {
                 if length > 0 {
                     debug!("Got size {} via HEAD for {}", length, url);
                     return Ok(length);
@@ -292,7 +1935,7 @@ async fn fetch_file_size(client: &reqwest::Client, url: &str) -> Result<u64> {
 
     // Stage 2: Fallback to GET request if HEAD fails or provides no size.
     debug!("HEAD failed or gave no size, falling back to GET for {}", url);
-    let get_resp = client.get(url).send().await?;
+    let get_resp = client.get(url).headers(headers.clone()).send().await?;
     
     if get_resp.status().is_success() {
         if let Some(length) = get_resp.content_length() {
@@ -305,7 +1948,138 @@ async fn fetch_file_size(client: &reqwest::Client, url: &str) -> Result<u64> {
 }
 
 
-fn truncate_filename(filename: &str, max_len: usize) -> String {
+/// Number of parts a file is split into: at most `split`, and few enough that every part
+/// is at least `min_split_size`. Returns 1 when the file should use a single stream.
+fn split_part_count(total_size: u64, split: usize, min_split_size: u64) -> usize {
+    if split <= 1 || total_size == 0 {
+        return 1;
+    }
+    let by_size = total_size / min_split_size.max(1);
+    (split as u64).min(by_size).max(1) as usize
+}
+
+/// Downloads a file of known size as `parts` concurrent range requests into `<dest>.part`,
+/// renaming it into place once every part succeeded. `added` tracks the bytes counted on the
+/// overall bar so the caller can roll them back on failure.
+async fn download_split(
+    task: &DownloadTask,
+    pb: &ProgressBar,
+    url: &str,
+    total_size: u64,
+    parts: usize,
+    added: &AtomicU64,
+) -> Result<()> {
+    let path = &task.destination_path;
+    let mut part_name = path.file_name().unwrap_or_default().to_os_string();
+    part_name.push(".part");
+    let part_path = path.with_file_name(part_name);
+    debug!("Splitting {} ({} bytes) into {} parts", url, total_size, parts);
+
+    let file = tokio::fs::File::create(&part_path)
+        .await
+        .with_context(|| format!("Failed to create {}", part_path.display()))?;
+    file.set_len(total_size).await?;
+    drop(file);
+
+    let part_size = total_size.div_ceil(parts as u64);
+    let ranges = (0..parts as u64)
+        .map(|i| (i * part_size, ((i + 1) * part_size).min(total_size) - 1))
+        .filter(|(start, end)| start <= end);
+    let part_futs = ranges.map(|(start, end)| {
+        let part_path = part_path.clone();
+        async move {
+            let resp = task
+                .client
+                .get(url)
+                .headers(task.headers_for(url))
+                .header(reqwest::header::ACCEPT_ENCODING, "identity")
+                .header(reqwest::header::RANGE, format!("bytes={}-{}", start, end))
+                .send()
+                .await?
+                .error_for_status()?;
+            if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                return Err(RangeNotSupportedError.into());
+            }
+            if !task.options.allow_html && is_unexpected_html(&resp, path) {
+                return Err(UnexpectedHtmlError { url: url.to_string(), preview: String::new() }.into());
+            }
+            if let Some(encoding) = resp.headers().get(reqwest::header::CONTENT_ENCODING) {
+                let encoding = encoding.to_str().unwrap_or_default().trim();
+                if !encoding.is_empty() && !encoding.eq_ignore_ascii_case("identity") {
+                    return Err(EncodedRangeError { url: url.to_string(), encoding: encoding.to_string() }.into());
+                }
+            }
+
+            let mut file = tokio::fs::OpenOptions::new().write(true).open(&part_path).await?;
+            file.seek(std::io::SeekFrom::Start(start)).await?;
+            let expected = end - start + 1;
+            let mut received = 0;
+            let mut stream = resp.bytes_stream();
+            loop {
+                task.control.checkpoint().await?;
+                let Some(chunk) = stream.next().await else {
+                    break;
+                };
+                let chunk = chunk.context("Failed to read chunk from download stream")?;
+                let chunk_len = (chunk.len() as u64).min(expected - received);
+                file.write_all(&chunk[..chunk_len as usize]).await.context("Failed to write chunk to file")?;
+                received += chunk_len;
+                task.tracker.record_transferred(chunk_len);
+                pb.inc(chunk_len);
+                task.overall_progress_bar.inc(chunk_len);
+                added.fetch_add(chunk_len, Ordering::Relaxed);
+                if received == expected {
+                    break;
+                }
+            }
+            file.flush().await?;
+            if received < expected {
+                return Err(anyhow!(
+                    "Part {}-{} of {} ended early: got {} of {} bytes",
+                    start, end, url, received, expected
+                ));
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+    });
+
+    let results: Vec<Result<()>> = futures_util::stream::iter(part_futs)
+        .buffer_unordered(parts)
+        .collect()
+        .await;
+    if let Some(err) = results.into_iter().find_map(Result::err) {
+        let _ = tokio::fs::remove_file(&part_path).await;
+        return Err(err);
+    }
+    tokio::fs::rename(&part_path, path)
+        .await
+        .with_context(|| format!("Failed to move {} into place", part_path.display()))?;
+    Ok(())
+}
+
+/// True when the response is `text/html` but the destination doesn't look like an HTML file.
+fn is_unexpected_html(resp: &reqwest::Response, path: &Path) -> bool {
+    let is_html = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().to_ascii_lowercase().starts_with("text/html"));
+    let expects_html = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_ascii_lowercase().as_str(), "html" | "htm" | "xhtml" | "shtml"));
+    is_html && !expects_html
+}
+
+/// Style for downloads whose total size is unknown: a spinner with a byte counter instead of a bar.
+fn indeterminate_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "{msg:30!} {spinner:.cyan} {bytes_formatted} @ {bytes_per_sec} │ size unknown"
+    ).expect("Invalid indeterminate progress bar template")
+     .with_key("bytes_formatted", |state: &ProgressState, w: &mut dyn FmtWrite| write!(w, "{}", format_bytes(state.pos())).unwrap())
+}
+
+pub fn truncate_filename(filename: &str, max_len: usize) -> String {
     if filename.chars().count() > max_len {
         let path = Path::new(filename);
         let stem = path.file_stem().unwrap_or_default().to_str().unwrap_or("");
@@ -327,6 +2101,7 @@ fn truncate_filename(filename: &str, max_len: usize) -> String {
 
 #[derive(Debug, Clone)]
 struct GGUFSeriesInfo {
+    /// Series name including its directory, e.g. `Q4_K_M/model-Q4_K_M`.
     base_name: String,
     total_parts: usize,
     files: Vec<(HFFile, u64)>,
@@ -340,7 +2115,28 @@ enum SelectableGGUFItem {
 }
 
 impl SelectableGGUFItem {
+    /// The listing line: quantization first, since that's what users choose by.
     fn display_name(&self) -> String {
+        format!("{:<8} {}", self.quant().unwrap_or_else(|| "-".to_string()), self.describe())
+    }
+
+    /// Quantization type parsed from the file or series name, if recognizable.
+    fn quant(&self) -> Option<String> {
+        let name = match self {
+            SelectableGGUFItem::Series(info) => info.base_name.as_str(),
+            SelectableGGUFItem::File(file, _) => file.filename.as_str(),
+        };
+        // The file name wins; shards kept in a per-quant folder (`Q4_K_M/model-00001-of-00002.gguf`)
+        // fall back to the nearest directory that names one.
+        name.rsplit('/').find_map(|component| {
+            GGUF_QUANT_REGEX
+                .captures(component)
+                .and_then(|caps| caps.get(1))
+                .map(|m| m.as_str().to_uppercase())
+        })
+    }
+
+    fn describe(&self) -> String {
         match self {
             SelectableGGUFItem::Series(info) => {
                 let completeness = if info.files.len() == info.total_parts && info.total_parts > 0 {
@@ -369,6 +2165,13 @@ impl SelectableGGUFItem {
         }
     }
 
+    fn total_size(&self) -> u64 {
+        match self {
+            SelectableGGUFItem::Series(info) => info.total_size,
+            SelectableGGUFItem::File(_, size) => *size,
+        }
+    }
+
     fn get_files(&self) -> Vec<HFFile> {
         match self {
             SelectableGGUFItem::Series(info) => info.files.iter().map(|(f, _)| f.clone()).collect(),
@@ -377,9 +2180,55 @@ impl SelectableGGUFItem {
     }
 }
 
+/// Splits a shard path like `Q4_K_M/model-00001-of-00003.gguf` into the key its series is grouped
+/// by: directory, base name and part count. `None` for files that aren't part of a series.
+pub fn gguf_series_key(filename: &str) -> Option<(&str, &str, usize)> {
+    let (directory, name) = filename.rsplit_once('/').unwrap_or(("", filename));
+    let caps = GGUF_SERIES_REGEX.captures(name)?;
+    let stem = caps.get(1)?.as_str();
+    let total_parts = caps.get(3)?.as_str().parse().unwrap_or(0);
+    Some((directory, stem, total_parts))
+}
+
+/// Groups shards into series keyed by (directory, base name, part count): shards are only grouped
+/// with siblings in the same folder, so equally named series in different folders stay separate.
+/// Everything else is returned as standalone files.
+fn group_gguf_series(files_with_sizes: Vec<(HFFile, u64)>) -> (Vec<GGUFSeriesInfo>, Vec<(HFFile, u64)>) {
+    let mut series_map: HashMap<(String, String, usize), GGUFSeriesInfo> = HashMap::new();
+    let mut standalone_files = Vec::new();
+
+    for (file, size) in files_with_sizes {
+        if let Some((directory, stem, total_parts)) = gguf_series_key(&file.filename) {
+            let base_name = if directory.is_empty() {
+                stem.to_string()
+            } else {
+                format!("{}/{}", directory, stem)
+            };
+            let series_key = (directory.to_string(), stem.to_string(), total_parts);
+
+            let entry = series_map.entry(series_key).or_insert_with(|| GGUFSeriesInfo {
+                base_name: base_name.clone(),
+                total_parts,
+                files: Vec::new(),
+                total_size: 0,
+            });
+            entry.files.push((file, size));
+            entry.total_size += size;
+        } else {
+            standalone_files.push((file, size));
+        }
+    }
+    (series_map.into_values().collect(), standalone_files)
+}
+
 pub async fn select_gguf_files(
     all_files: Vec<HFFile>,
     hf_token: &str,
+    auto_select: Option<AutoSelect>,
+    group_by_quant: bool,
+    prescan_concurrency: usize,
+    force_prescan: bool,
+    client_options: ClientOptions,
 ) -> Result<Vec<HFFile>> {
     eprintln!("[INFO] Identifying GGUF files and series for selection...");
     let gguf_files: Vec<_> = all_files.into_iter().filter(|f| f.filename.to_lowercase().ends_with(".gguf")).collect();
@@ -390,7 +2239,7 @@ pub async fn select_gguf_files(
     }
     
     eprintln!("[INFO] Fetching sizes for {} GGUF file(s)...", gguf_files.len());
-    let client = get_client(hf_token)?;
+    let client = get_client(hf_token, client_options)?;
     let pb = ProgressBar::new(gguf_files.len() as u64);
     pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({percent}%)").unwrap());
 
@@ -402,10 +2251,22 @@ pub async fn select_gguf_files(
         let error_count = error_count.clone();
 
         async move {
-            let size_res = fetch_file_size(&client, &file.url).await;
+            if let (Some(size), false) = (file.size, force_prescan) {
+                pb_clone.inc(1);
+                return (file, size);
+            }
+            let size_res = fetch_file_size(&client, &file.url, &HeaderMap::new()).await;
             pb_clone.inc(1);
             match size_res {
-                Ok(size) => (file, size),
+                Ok(size) => {
+                    log_size_discrepancy(&file.filename, file.size, size);
+                    (file, size)
+                }
+                Err(e) if file.size.is_some() => {
+                    debug!("Failed to get size for {}, keeping declared size: {}", file.filename, e);
+                    let size = file.size.unwrap_or(0);
+                    (file, size)
+                }
                 Err(e) => {
                     log::warn!("Failed to get size for {}: {}", file.filename, e);
                     let current_errors = error_count.fetch_add(1, Ordering::SeqCst);
@@ -421,36 +2282,23 @@ pub async fn select_gguf_files(
     });
     
     let stream = futures_util::stream::iter(size_futs);
-    let files_with_sizes: Vec<(HFFile, u64)> = stream.buffer_unordered(PRESCAN_CONCURRENCY).collect().await;
+    let files_with_sizes: Vec<(HFFile, u64)> = stream.buffer_unordered(prescan_concurrency).collect().await;
     pb.finish_and_clear();
 
-    let mut series_map: HashMap<String, GGUFSeriesInfo> = HashMap::new();
-    let mut standalone_files = Vec::new();
-
-    for (file, size) in files_with_sizes {
-        if let Some(caps) = GGUF_SERIES_REGEX.captures(&file.filename) {
-            let base_name = caps.get(1).unwrap().as_str().to_string();
-            let total_parts: usize = caps.get(3).unwrap().as_str().parse().unwrap_or(0);
-            let series_key = format!("{}-of-{}", base_name, total_parts);
-            
-            let entry = series_map.entry(series_key).or_insert_with(|| GGUFSeriesInfo {
-                base_name: base_name.clone(),
-                total_parts,
-                files: Vec::new(),
-                total_size: 0,
-            });
-            entry.files.push((file, size));
-            entry.total_size += size;
-        } else {
-            standalone_files.push((file, size));
-        }
-    }
-    
+    let (series, standalone_files) = group_gguf_series(files_with_sizes);
     let mut selectable_items: Vec<SelectableGGUFItem> = Vec::new();
-    selectable_items.extend(series_map.into_values().map(SelectableGGUFItem::Series));
+    selectable_items.extend(series.into_iter().map(SelectableGGUFItem::Series));
     selectable_items.extend(standalone_files.into_iter().map(|(f,s)| SelectableGGUFItem::File(f,s)));
+    if group_by_quant {
+        // Unrecognized quants sort after all known ones.
+        selectable_items.sort_by_key(|a| (a.quant().is_none(), a.quant(), a.describe()));
+    } else {
+        selectable_items.sort_by_key(|a| a.describe());
+    }
 
-    selectable_items.sort_by(|a, b| a.display_name().cmp(&b.display_name()));
+    if let Some(mode) = auto_select {
+        return auto_select_item(&selectable_items, mode);
+    }
 
     eprintln!("\nAvailable GGUF files/series for download:");
     for (i, item) in selectable_items.iter().enumerate() {
@@ -511,4 +2359,164 @@ pub async fn select_gguf_files(
             return Ok(unique_files.into_values().collect());
         }
     }
-}
\ No newline at end of file
+}
+
+/// Picks the single smallest or largest complete item whose size is known.
+fn auto_select_item(items: &[SelectableGGUFItem], mode: AutoSelect) -> Result<Vec<HFFile>> {
+    let candidates = items
+        .iter()
+        .filter(|item| item.is_complete() && item.total_size() > 0);
+    let chosen = match mode {
+        AutoSelect::Smallest => candidates.min_by_key(|item| item.total_size()),
+        AutoSelect::Largest => candidates.max_by_key(|item| item.total_size()),
+    };
+    match chosen {
+        Some(item) => {
+            eprintln!("[INFO] Auto-selected {}", item.display_name());
+            Ok(item.get_files())
+        }
+        None => Err(anyhow!(
+            "No complete GGUF file or series with a known size is available for auto-selection."
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shards(names: &[&str]) -> Vec<(HFFile, u64)> {
+        names
+            .iter()
+            .map(|name| {
+                let file = HFFile {
+                    url: format!("https://example.com/{}", name),
+                    filename: name.to_string(),
+                    sha256: None,
+                    size: None,
+                };
+                (file, 10)
+            })
+            .collect()
+    }
+
+    fn series_names(series: &[GGUFSeriesInfo]) -> Vec<(String, usize)> {
+        let mut names: Vec<_> = series.iter().map(|s| (s.base_name.clone(), s.files.len())).collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn mismatching_manifest_digest_fails_the_item() {
+        let dir = std::env::temp_dir().join(format!("dl-test-{}-manifest-digest", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("manifest.json");
+        let declared = "0".repeat(64);
+        std::fs::write(
+            &manifest,
+            format!(r#"[{{"url": "https://example.com/a.bin", "sha256": "{}"}}]"#, declared),
+        )
+        .unwrap();
+        let items = crate::manifest::read_manifest(&manifest).await.unwrap();
+
+        // Checked without --strict-verify: the manifest declared it.
+        let expected = expected_sha256(&items[0], false).unwrap();
+        assert_eq!(expected, declared);
+
+        let file = dir.join("a.bin");
+        std::fs::write(&file, b"not what the manifest declared").unwrap();
+        let err = verify_file(&file, expected, true).await.unwrap_err();
+        assert!(err.downcast_ref::<ChecksumMismatchError>().is_some(), "{:#}", err);
+        assert!(!file.exists(), "the mismatching file should be deleted");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn item_headers_are_not_sent_to_mirrors_on_other_hosts() {
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::AUTHORIZATION, "Bearer primary-token".parse().unwrap());
+        headers.insert(reqwest::header::COOKIE, "session=1".parse().unwrap());
+        headers.insert("x-custom", "1".parse().unwrap());
+        let item = DownloadItem {
+            url: "https://files.example.com/a.bin".to_string(),
+            mirrors: vec![
+                "https://FILES.example.com:443/mirror/a.bin".to_string(),
+                "https://mirror.example.org/a.bin".to_string(),
+            ],
+            headers,
+            ..Default::default()
+        };
+        let auth = vec![crate::util::parse_host_auth("mirror.example.org=bearer:mirror-token").unwrap()];
+
+        let primary = item.request_headers(&item.url, &auth);
+        assert_eq!(primary[reqwest::header::AUTHORIZATION], "Bearer primary-token");
+        assert!(primary.contains_key(reqwest::header::COOKIE));
+
+        let same_host_mirror = item.request_headers(&item.mirrors[0], &auth);
+        assert_eq!(same_host_mirror[reqwest::header::AUTHORIZATION], "Bearer primary-token");
+
+        let other_host_mirror = item.request_headers(&item.mirrors[1], &auth);
+        assert!(!other_host_mirror.contains_key(reqwest::header::COOKIE));
+        assert!(!other_host_mirror.contains_key("x-custom"));
+        // The mirror still gets the --auth credentials scoped to its own host.
+        assert_eq!(other_host_mirror[reqwest::header::AUTHORIZATION], "Bearer mirror-token");
+    }
+
+    #[test]
+    fn hugging_face_digests_are_checked_only_with_strict_verify() {
+        let item = DownloadItem {
+            url: "https://example.com/a.bin".to_string(),
+            sha256: Some("0".repeat(64)),
+            checksum_source: Some(ChecksumSource::HuggingFace),
+            ..Default::default()
+        };
+        assert_eq!(expected_sha256(&item, false), None);
+        assert!(expected_sha256(&item, true).is_some());
+    }
+
+    #[test]
+    fn same_named_series_in_different_folders_stay_separate() {
+        let (series, standalone) = group_gguf_series(shards(&[
+            "Q4_K_M/model-00001-of-00002.gguf",
+            "Q8_0/model-00001-of-00002.gguf",
+            "Q4_K_M/model-00002-of-00002.gguf",
+            "Q8_0/model-00002-of-00002.gguf",
+        ]));
+        assert!(standalone.is_empty());
+        assert_eq!(
+            series_names(&series),
+            vec![("Q4_K_M/model".to_string(), 2), ("Q8_0/model".to_string(), 2)]
+        );
+        assert!(series.iter().all(|s| s.total_parts == 2 && s.total_size == 20));
+    }
+
+    #[test]
+    fn series_in_one_subfolder_is_grouped_with_its_directory() {
+        assert_eq!(
+            gguf_series_key("big/Q4_K_M/model-00002-of-00003.gguf"),
+            Some(("big/Q4_K_M", "model", 3))
+        );
+        let (series, standalone) = group_gguf_series(shards(&[
+            "Q4_K_M/model-00001-of-00003.gguf",
+            "Q4_K_M/model-00002-of-00003.gguf",
+            "Q4_K_M/model-00003-of-00003.gguf",
+            "model-Q8_0.gguf",
+        ]));
+        assert_eq!(series_names(&series), vec![("Q4_K_M/model".to_string(), 3)]);
+        assert_eq!(standalone.len(), 1);
+        assert_eq!(standalone[0].0.filename, "model-Q8_0.gguf");
+    }
+
+    #[test]
+    fn root_level_shards_keep_a_bare_name() {
+        assert_eq!(gguf_series_key("model-00001-of-00002.gguf"), Some(("", "model", 2)));
+        assert_eq!(gguf_series_key("model-Q4_K_M.gguf"), None);
+        let (series, standalone) = group_gguf_series(shards(&[
+            "model-00001-of-00002.gguf",
+            "model-00002-of-00002.gguf",
+        ]));
+        assert!(standalone.is_empty());
+        assert_eq!(series_names(&series), vec![("model".to_string(), 2)]);
+    }
+}
@@ -1,6 +1,9 @@
-use crate::util::get_client;
+use crate::metadata_cache;
+use crate::util::{clean_repo_id, get_api_client, natural_cmp, path_matches_glob, ClientOptions};
 use anyhow::{Context, Result};
 use log::debug;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
 use serde::Deserialize;
 use urlencoding::encode;
 
@@ -9,11 +12,26 @@ pub struct HFFile {
     pub url: String,
     #[serde(rename = "rfilename")]
     pub filename: String,
+    /// SHA-256 of LFS-tracked files, as reported by the Hub.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// File size in bytes, as reported by the Hub.
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LfsInfo {
+    sha256: String,
 }
 
 #[derive(Deserialize, Debug)]
 struct Sibling {
     rfilename: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    lfs: Option<LfsInfo>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -21,21 +39,66 @@ struct RepoInfo {
     siblings: Vec<Sibling>,
 }
 
-pub async fn fetch_hugging_face_urls(repo_id: &str, hf_token: &str) -> Result<Vec<HFFile>> {
-    let repo_id_clean = repo_id
-        .trim_start_matches("https://huggingface.co/")
-        .trim_start_matches("http://huggingface.co/");
+pub async fn fetch_hugging_face_urls(
+    repo_id: &str,
+    hf_token: &str,
+    endpoint: &str,
+    client_options: ClientOptions,
+) -> Result<Vec<HFFile>> {
+    let repo_id_clean = clean_repo_id(repo_id, endpoint);
 
-    let api_url = format!("https://huggingface.co/api/models/{}", repo_id_clean);
+    // `blobs=true` makes the Hub include sizes and LFS hashes for each sibling.
+    let api_url = format!("{}/api/models/{}?blobs=true", endpoint, repo_id_clean);
     debug!("Fetching HF repo info from: {}", api_url);
 
-    let client = get_client(hf_token)?;
-    let resp = client
-        .get(&api_url)
+    let repo_info = fetch_repo_info(&api_url, hf_token, client_options).await?;
+
+    let hf_files: Vec<HFFile> = repo_info
+        .siblings
+        .into_iter()
+        .map(|sibling| {
+            HFFile {
+                url: resolve_url(endpoint, repo_id_clean, &sibling.rfilename),
+                filename: sibling.rfilename,
+                sha256: sibling.lfs.map(|lfs| lfs.sha256),
+                size: sibling.size,
+            }
+        })
+        .collect();
+
+    debug!("Found {} files in repo {}", hf_files.len(), repo_id);
+    Ok(hf_files)
+}
+
+/// Fetches a repo listing through the metadata cache: a fresh entry is used as is, and a stale one
+/// is revalidated with its ETag, so an unchanged repo costs a 304 instead of the whole listing.
+async fn fetch_repo_info(api_url: &str, hf_token: &str, client_options: ClientOptions) -> Result<RepoInfo> {
+    let policy = client_options.metadata_cache;
+    let parse = |body: &str| -> Result<RepoInfo> {
+        serde_json::from_str(body).with_context(|| "Failed to decode JSON response from Hugging Face API")
+    };
+    let cached = metadata_cache::load(api_url, hf_token, policy).await;
+    if let Some(cached) = cached.as_ref().filter(|c| c.fresh) {
+        return parse(&cached.body);
+    }
+
+    let client = get_api_client(hf_token, client_options)?;
+    let mut request = client.get(api_url);
+    if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_deref()) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let resp = request
         .send()
         .await
         .with_context(|| format!("Failed to send request to HF API at {}", api_url))?;
 
+    if let (StatusCode::NOT_MODIFIED, Some(cached)) = (resp.status(), &cached) {
+        debug!("{} is unchanged; using the cached listing", api_url);
+        let repo_info = parse(&cached.body)?;
+        // Confirmed current, so its --max-age window starts over.
+        metadata_cache::store(api_url, hf_token, policy, &cached.body, cached.etag.as_deref()).await;
+        return Ok(repo_info);
+    }
     if !resp.status().is_success() {
         let status = resp.status();
         let error_body = resp.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
@@ -45,34 +108,108 @@ pub async fn fetch_hugging_face_urls(repo_id: &str, hf_token: &str) -> Result<Ve
             error_body
         ));
     }
-    
-    let repo_info = resp
-        .json::<RepoInfo>()
+
+    let etag = resp.headers().get(ETAG).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let body = resp
+        .text()
         .await
-        .with_context(|| "Failed to decode JSON response from Hugging Face API")?;
+        .with_context(|| format!("Failed to read response from HF API at {}", api_url))?;
+    let repo_info = parse(&body)?;
+    metadata_cache::store(api_url, hf_token, policy, &body, etag.as_deref()).await;
+    Ok(repo_info)
+}
 
-    let branch = "main";
-    let hf_files: Vec<HFFile> = repo_info
-        .siblings
+/// Builds the download URL of a file on the `main` branch of a repo.
+pub fn resolve_url(endpoint: &str, repo_id: &str, path: &str) -> String {
+    let safe_path = path.split('/').map(encode).collect::<Vec<_>>().join("/");
+    format!("{}/{}/resolve/main/{}?download=true", endpoint, repo_id, safe_path)
+}
+
+/// A single repo file named with the `hf://owner/repo/path` or `owner/repo:path` shorthand.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HFFileRef {
+    pub repo_id: String,
+    pub path: String,
+}
+
+/// Parses the single-file shorthand; returns `None` for anything else, such as a plain URL.
+pub fn parse_hf_shorthand(input: &str) -> Option<HFFileRef> {
+    let (repo_id, path) = if let Some(rest) = input.strip_prefix("hf://") {
+        let mut parts = rest.splitn(3, '/');
+        let (owner, repo, path) = (parts.next()?, parts.next()?, parts.next()?);
+        (format!("{}/{}", owner, repo), path)
+    } else if !input.contains("://") {
+        let (repo_id, path) = input.split_once(':')?;
+        (repo_id.to_string(), path)
+    } else {
+        return None;
+    };
+    let path = path.trim_matches('/');
+    let valid_name = |s: &str| {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    let (owner, repo) = repo_id.split_once('/')?;
+    if !valid_name(owner) || !valid_name(repo) || path.is_empty() {
+        return None;
+    }
+    Some(HFFileRef { repo_id, path: path.to_string() })
+}
+
+/// The file matching `glob` with the highest version, comparing paths in natural order (see
+/// `natural_cmp`), e.g. `model-v1.10.gguf` over `model-v1.9.gguf`.
+pub fn select_latest(files: Vec<HFFile>, glob: &str) -> Option<HFFile> {
+    files
         .into_iter()
-        .map(|sibling| {
-            let safe_rfilename_path = sibling
-                .rfilename
-                .split('/')
-                .map(encode)
-                .collect::<Vec<_>>()
-                .join("/");
-            let url = format!(
-                "https://huggingface.co/{}/resolve/{}/{}?download=true",
-                repo_id_clean, branch, safe_rfilename_path
-            );
-            HFFile {
-                url,
-                filename: sibling.rfilename,
-            }
-        })
-        .collect();
+        .filter(|f| path_matches_glob(&f.filename, glob))
+        .max_by(|a, b| natural_cmp(&a.filename, &b.filename))
+}
 
-    debug!("Found {} files in repo {}", hf_files.len(), repo_id);
-    Ok(hf_files)
-}
\ No newline at end of file
+/// Keeps files matching any include pattern (all files if there are none), then drops
+/// files matching any exclude pattern.
+pub fn filter_hf_files(files: Vec<HFFile>, include: &[String], exclude: &[String]) -> Vec<HFFile> {
+    files
+        .into_iter()
+        .filter(|f| include.is_empty() || include.iter().any(|p| path_matches_glob(&f.filename, p)))
+        .filter(|f| !exclude.iter().any(|p| path_matches_glob(&f.filename, p)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<HFFile> {
+        names
+            .iter()
+            .map(|name| HFFile {
+                url: format!("https://example.com/{}", name),
+                filename: name.to_string(),
+                sha256: None,
+                size: None,
+            })
+            .collect()
+    }
+
+    fn latest(names: &[&str], glob: &str) -> Option<String> {
+        select_latest(files(names), glob).map(|f| f.filename)
+    }
+
+    #[test]
+    fn select_latest_compares_versions_numerically() {
+        let names = ["model-v1.9.gguf", "model-v1.10.gguf", "model-v1.2.gguf", "README.md"];
+        assert_eq!(latest(&names, "model-v*.gguf").as_deref(), Some("model-v1.10.gguf"));
+        let names = ["model-v1.9.gguf", "model-v2.gguf"];
+        assert_eq!(latest(&names, "model-v*.gguf").as_deref(), Some("model-v2.gguf"));
+    }
+
+    #[test]
+    fn select_latest_prefers_releases_over_pre_releases() {
+        let names = ["model-v1.3-rc1.gguf", "model-v1.3.gguf", "model-v1.2.gguf"];
+        assert_eq!(latest(&names, "model-v*.gguf").as_deref(), Some("model-v1.3.gguf"));
+    }
+
+    #[test]
+    fn select_latest_without_a_match_is_none() {
+        assert_eq!(latest(&["model-v1.gguf"], "other-*.gguf"), None);
+    }
+}
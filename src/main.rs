@@ -1,21 +1,34 @@
 use anyhow::Result;
 use clap::Parser;
-use log::info;
+use log::{debug, info};
+use std::fmt::Write as _;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+mod cas;
+mod checksum;
+mod cleanup;
 mod cli;
 mod config;
+mod control;
 mod downloader;
 mod hf;
+mod manifest;
+mod metadata_cache;
+mod naming;
+mod progress;
+mod repair;
 mod search;
+mod status;
 mod updater;
 mod util;
 
 use cli::{Cli, Commands, ModelCommands};
-use downloader::{run_downloads, DownloadItem};
-use hf::fetch_hugging_face_urls;
+use downloader::{run_downloads, ChecksumSource, DownloadItem, DownloadOptions, TopFiles};
+use hf::{fetch_hugging_face_urls, filter_hf_files};
 use search::handle_model_search;
-use updater::handle_update;
+use status::handle_status;
+use updater::{handle_list_assets, handle_update};
 use util::log_panic;
 
 #[tokio::main]
@@ -36,18 +49,61 @@ async fn main() -> Result<()> {
         String::new()
     };
 
+    let client_options = util::ClientOptions {
+        min_tls: cli.min_tls,
+        api_timeout: (cli.api_timeout > 0).then(|| std::time::Duration::from_secs(cli.api_timeout)),
+        metadata_cache: match (cli.no_cache, cli.max_age) {
+            (false, Some(max_age)) => metadata_cache::CachePolicy::MaxAge(max_age),
+            _ => metadata_cache::CachePolicy::Disabled,
+        },
+        ip_preference: if cli.prefer_ipv4 {
+            Some(cli::IpPreference::V4)
+        } else if cli.prefer_ipv6 {
+            Some(cli::IpPreference::V6)
+        } else {
+            None
+        },
+    };
+
+    let endpoint = resolve_hf_endpoint(cli.endpoint.as_deref())?;
+    debug!("Using Hugging Face endpoint: {}", endpoint);
+
     match cli.command {
         Some(Commands::Model { command }) => match command {
-            ModelCommands::Search { query } => {
-                handle_model_search(&query.join(" "), &hf_token).await?;
+            ModelCommands::Search { query, has_gguf } => {
+                handle_model_search(&query.join(" "), &hf_token, &endpoint, has_gguf, client_options).await?;
+            }
+            ModelCommands::Aliases => {
+                let mut aliases: Vec<&str> = config::get_model_registry().keys().copied().collect();
+                aliases.sort_unstable();
+                // Stop quietly when the reader (e.g. a completion function or `head`) goes away.
+                let mut stdout = std::io::stdout().lock();
+                for alias in aliases {
+                    if writeln!(stdout, "{}", alias).is_err() {
+                        break;
+                    }
+                }
             }
         },
-        Some(Commands::UpdateApp) => {
-            handle_update().await?;
+        Some(Commands::Status { file }) => {
+            handle_status(&file)?;
+        }
+        Some(Commands::Version { json }) => {
+            print_version(json)?;
+        }
+        Some(Commands::UpdateApp { github_token, list_assets, release, since_release }) => {
+            let github_token = github_token
+                .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+                .unwrap_or_default();
+            if list_assets {
+                handle_list_assets(&github_token, release.as_deref(), client_options).await?;
+            } else {
+                handle_update(&github_token, since_release, client_options).await?;
+            }
         }
         None => {
             // This is the downloader path
-            run_downloader_flow(cli, &hf_token).await?;
+            run_downloader_flow(cli, &hf_token, &endpoint, client_options).await?;
         }
     }
 
@@ -55,6 +111,37 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Build details for `dl version`, e.g. for bug reports and packaging scripts.
+#[derive(serde::Serialize)]
+struct VersionInfo {
+    name: &'static str,
+    version: &'static str,
+    git_commit: Option<&'static str>,
+    target: &'static str,
+    updater_repo: String,
+}
+
+fn print_version(json: bool) -> Result<()> {
+    let info = VersionInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: config::CURRENT_APP_VERSION,
+        git_commit: config::GIT_COMMIT,
+        target: config::BUILD_TARGET,
+        updater_repo: format!("{}/{}", config::UPDATER_REPO_OWNER, config::UPDATER_REPO_NAME),
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        println!("{} {}", info.name, info.version);
+        if let Some(commit) = info.git_commit {
+            println!("Commit:  {}", commit);
+        }
+        println!("Target:  {}", info.target);
+        println!("Updates: https://github.com/{}", info.updater_repo);
+    }
+    Ok(())
+}
+
 fn setup_logging_for_debug(debug: bool) -> Result<()> {
     if debug {
         fern::Dispatch::new()
@@ -76,12 +163,78 @@ fn setup_logging_for_debug(debug: bool) -> Result<()> {
     Ok(())
 }
 
+/// The run's start time in the `--timestamp-dir` strftime format, made safe as a directory name.
+fn timestamp_dir_name(format: &str) -> Result<String> {
+    let mut name = String::new();
+    write!(name, "{}", chrono::Local::now().format(format))
+        .map_err(|_| anyhow::anyhow!("Invalid --timestamp-format '{}'", format))?;
+    let name = util::sanitize_filename(name.trim());
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(anyhow::anyhow!("--timestamp-format '{}' gives an empty directory name", format));
+    }
+    Ok(name)
+}
+
+/// Combines explicit --include globs with the file-type shortcut flags.
+fn include_patterns(cli: &Cli) -> Vec<String> {
+    let mut patterns = cli.include.clone();
+    let shortcuts = [
+        (cli.gguf, config::GGUF_PATTERNS),
+        (cli.safetensors, config::SAFETENSORS_PATTERNS),
+        (cli.only_config, config::CONFIG_PATTERNS),
+    ];
+    for (enabled, shortcut_patterns) in shortcuts {
+        if enabled {
+            patterns.extend(shortcut_patterns.iter().map(|p| p.to_string()));
+        }
+    }
+    patterns
+}
+
+/// Picks the Hugging Face endpoint from --endpoint, then HF_ENDPOINT, then the default.
+fn resolve_hf_endpoint(cli_endpoint: Option<&str>) -> Result<String> {
+    let env_endpoint = std::env::var("HF_ENDPOINT").ok().filter(|e| !e.trim().is_empty());
+    let endpoint = cli_endpoint
+        .map(str::to_string)
+        .or(env_endpoint)
+        .unwrap_or_else(|| config::DEFAULT_HF_ENDPOINT.to_string());
+    util::normalize_endpoint(&endpoint)
+}
+
+/// Refuses to query the Hugging Face API when --allow-host/--deny-host exclude its host.
+fn ensure_endpoint_permitted(allow: &[String], deny: &[String], endpoint: &str) -> Result<()> {
+    if util::host_permitted(endpoint, allow, deny) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "The Hugging Face endpoint {} is not allowed by --allow-host/--deny-host.",
+            endpoint
+        ))
+    }
+}
+
+async fn run_downloader_flow(
+    cli: Cli,
+    hf_token: &str,
+    endpoint: &str,
+    client_options: util::ClientOptions,
+) -> Result<()> {
+    let metered = cli.metered
+        || std::env::var("DL_METERED").is_ok_and(|v| matches!(v.trim(), "1" | "true" | "yes"));
+    let (concurrency, prescan_concurrency) = if metered {
+        eprintln!("[INFO] Metered mode: downloading one file at a time and confirming large runs.");
+        (1, 1)
+    } else {
+        (cli.concurrency, downloader::PRESCAN_CONCURRENCY)
+    };
 
-async fn run_downloader_flow(cli: Cli, hf_token: &str) -> Result<()> {
     let mut modes_set = 0;
     if cli.file.is_some() {
         modes_set += 1;
     }
+    if cli.manifest.is_some() {
+        modes_set += 1;
+    }
     if cli.hf.is_some() {
         modes_set += 1;
     }
@@ -94,59 +247,145 @@ async fn run_downloader_flow(cli: Cli, hf_token: &str) -> Result<()> {
 
     if modes_set == 0 {
         return Err(anyhow::anyhow!(
-            "No download source provided. Use URLs, -f, -h, or -m. Use --help for more info."
+            "No download source provided. Use URLs, -f, --manifest, -h, or -m. Use --help for more info."
         ));
     }
     if modes_set > 1 {
         return Err(anyhow::anyhow!(
-            "Flags -f, -h, -m, and direct URLs are mutually exclusive."
+            "Flags -f, --manifest, -h, -m, and direct URLs are mutually exclusive."
         ));
     }
 
     let mut download_items = Vec::new();
     let mut download_dir = PathBuf::from("downloads");
+    if cli.timestamp_dir {
+        download_dir.push(timestamp_dir_name(&cli.timestamp_format)?);
+        eprintln!("[INFO] Downloading into {}", download_dir.display());
+    }
+    // With --repair: every selected file and the folder they belong in, checked after downloading.
+    let mut repair_check = None;
 
-    if let Some(model_alias) = cli.model {
+    if let Some(manifest_path) = &cli.manifest {
+        download_items = manifest::read_manifest(manifest_path).await?;
+    } else if let Some(model_alias) = cli.model {
         let registry = config::get_model_registry();
-        if let Some(url) = registry.get(model_alias.as_str()) {
-            let preferred_filename = Path::new(url)
-                .file_name()
-                .and_then(|f| f.to_str())
-                .unwrap_or("download.file")
-                .to_string();
-            download_items.push(DownloadItem {
-                url: url.to_string(),
-                preferred_filename: Some(preferred_filename),
-            });
-            download_dir.push(util::sanitize_filename(&model_alias));
-        } else {
-            return Err(anyhow::anyhow!("Model alias '{}' not found in the registry.", model_alias));
+        match registry.get(model_alias.as_str()) {
+            Some(config::ModelSource::Url(registry_url)) => {
+                // Registry entries point at the public hub; redirect them to the configured endpoint.
+                let url = match registry_url.strip_prefix(config::DEFAULT_HF_ENDPOINT) {
+                    Some(rest) => format!("{}{}", endpoint, rest),
+                    None => registry_url.to_string(),
+                };
+                let preferred_filename = Path::new(&url)
+                    .file_name()
+                    .and_then(|f| f.to_str())
+                    .unwrap_or("download.file")
+                    .to_string();
+                download_items.push(DownloadItem {
+                    url,
+                    preferred_filename: Some(preferred_filename),
+                    ..Default::default()
+                });
+            }
+            Some(config::ModelSource::Repo { repo, pattern }) => {
+                ensure_endpoint_permitted(&cli.allow_host, &cli.deny_host, endpoint)?;
+                eprintln!("[INFO] Resolving '{}' from {} (files matching '{}')", model_alias, repo, pattern);
+                let matching: Vec<_> = fetch_hugging_face_urls(repo, hf_token, endpoint, client_options)
+                    .await?
+                    .into_iter()
+                    .filter(|f| util::glob_match(pattern, &f.filename))
+                    .collect();
+                if matching.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "No files in {} match '{}' for model alias '{}'.",
+                        repo,
+                        pattern,
+                        model_alias
+                    ));
+                }
+                for hf_file in matching {
+                    download_items.push(DownloadItem {
+                        url: hf_file.url,
+                        preferred_filename: Some(hf_file.filename),
+                        checksum_source: hf_file.sha256.is_some().then_some(ChecksumSource::HuggingFace),
+                        sha256: hf_file.sha256,
+                        size: hf_file.size,
+                        ..Default::default()
+                    });
+                }
+            }
+            None => {
+                return Err(anyhow::anyhow!("Model alias '{}' not found in the registry.", model_alias));
+            }
         }
-    } else if let Some(hf_repo) = cli.hf {
+        download_dir.push(util::sanitize_filename(&model_alias));
+    } else if let Some(hf_repo) = cli.hf.clone() {
+        ensure_endpoint_permitted(&cli.allow_host, &cli.deny_host, endpoint)?;
         eprintln!("[INFO] Fetching file list from Hugging Face repository: {}", hf_repo);
-        let all_repo_files = fetch_hugging_face_urls(&hf_repo, hf_token).await?;
+        let all_repo_files = fetch_hugging_face_urls(&hf_repo, hf_token, endpoint, client_options).await?;
         if all_repo_files.is_empty() {
             eprintln!("[INFO] No files found in the repository. Exiting.");
             return Ok(());
         }
 
-        let files_to_download = if cli.select {
+        let include = include_patterns(&cli);
+        let listed = all_repo_files.len();
+        let all_repo_files = filter_hf_files(all_repo_files, &include, &cli.exclude);
+        if all_repo_files.len() != listed {
+            eprintln!("[INFO] {} of {} file(s) match the include/exclude filters.", all_repo_files.len(), listed);
+        }
+        if all_repo_files.is_empty() {
+            eprintln!("[INFO] No files left after filtering. Exiting.");
+            return Ok(());
+        }
+        let all_repo_files = match &cli.latest {
+            Some(glob) => match hf::select_latest(all_repo_files, glob) {
+                Some(latest) => {
+                    eprintln!("[INFO] Latest file matching '{}': {}", glob, latest.filename);
+                    vec![latest]
+                }
+                None => return Err(anyhow::anyhow!("No files in {} match --latest '{}'.", hf_repo, glob)),
+            },
+            None => all_repo_files,
+        };
+
+        let files_to_download = if cli.select || cli.auto_select.is_some() {
             // The Fix: `select_gguf_files` now manages its own concurrency and no longer needs the `cli.concurrency` argument.
-            downloader::select_gguf_files(all_repo_files, hf_token).await?
+            downloader::select_gguf_files(
+                all_repo_files,
+                hf_token,
+                cli.auto_select,
+                cli.group_by_quant,
+                prescan_concurrency,
+                cli.force_prescan,
+                client_options,
+            )
+            .await?
         } else {
             all_repo_files
         };
 
+        let safe_repo_name = util::repo_id_to_safe_path(&hf_repo, endpoint);
+        download_dir.push(safe_repo_name);
+
+        let files_to_download = if cli.repair {
+            let to_download = repair::plan_repair(files_to_download.clone(), &download_dir).await?;
+            repair_check = Some((files_to_download, download_dir.clone()));
+            to_download
+        } else {
+            files_to_download
+        };
+
         for hf_file in files_to_download {
             download_items.push(DownloadItem {
                 url: hf_file.url,
                 preferred_filename: Some(hf_file.filename),
+                checksum_source: hf_file.sha256.is_some().then_some(ChecksumSource::HuggingFace),
+                sha256: hf_file.sha256,
+                size: hf_file.size,
+                ..Default::default()
             });
         }
-        
-        let safe_repo_name = util::repo_id_to_safe_path(&hf_repo);
-        download_dir.push(safe_repo_name);
-
     } else {
         let mut input_urls = cli.urls;
         if let Some(file_path) = cli.file {
@@ -159,23 +398,158 @@ async fn run_downloader_flow(cli: Cli, hf_token: &str) -> Result<()> {
             input_urls.extend(urls_from_file);
         }
         for url in input_urls {
+            if let Some(file_ref) = hf::parse_hf_shorthand(&url) {
+                debug!("Resolved shorthand '{}' to {}:{}", url, file_ref.repo_id, file_ref.path);
+                // Same layout as -H: the file goes under the repo's directory.
+                let repo_dir = util::repo_id_to_safe_path(&file_ref.repo_id, endpoint);
+                download_items.push(DownloadItem {
+                    url: hf::resolve_url(endpoint, &file_ref.repo_id, &file_ref.path),
+                    preferred_filename: Some(format!("{}/{}", repo_dir, file_ref.path)),
+                    ..Default::default()
+                });
+                continue;
+            }
             download_items.push(DownloadItem {
                 url,
                 preferred_filename: None,
+                ..Default::default()
             });
         }
     }
     
+    if !cli.allow_host.is_empty() || !cli.deny_host.is_empty() {
+        download_items.retain_mut(|item| {
+            let permitted = util::host_permitted(&item.url, &cli.allow_host, &cli.deny_host);
+            if !permitted {
+                eprintln!("[WARN] Skipping {}: host is not allowed by --allow-host/--deny-host.", item.url);
+            }
+            item.mirrors.retain(|mirror| {
+                let permitted = util::host_permitted(mirror, &cli.allow_host, &cli.deny_host);
+                if !permitted {
+                    eprintln!("[WARN] Ignoring mirror {}: host is not allowed by --allow-host/--deny-host.", mirror);
+                }
+                permitted
+            });
+            permitted
+        });
+    }
+
+    if let Some(checksums_url) = &cli.checksums_url {
+        if !util::host_permitted(checksums_url, &cli.allow_host, &cli.deny_host) {
+            return Err(anyhow::anyhow!("--checksums-url {} is not allowed by --allow-host/--deny-host.", checksums_url));
+        }
+        manifest::apply_checksums_url(&mut download_items, checksums_url, &cli.auth, client_options).await?;
+    }
+
     if download_items.is_empty() {
+        if let Some((files, dir)) = &repair_check {
+            return repair::verify_repaired(files, dir).await;
+        }
         eprintln!("[INFO] No files to download. Exiting.");
         return Ok(());
     }
 
-    if !download_dir.exists() {
-        tokio::fs::create_dir_all(&download_dir).await?;
+    if cli.shuffle {
+        let mut rng = match cli.seed {
+            Some(seed) => fastrand::Rng::with_seed(seed),
+            None => fastrand::Rng::new(),
+        };
+        rng.shuffle(&mut download_items);
+        debug!("Shuffled {} download(s) (seed: {:?})", download_items.len(), cli.seed);
     }
 
-    run_downloads(download_items, download_dir, cli.concurrency, hf_token.to_string()).await?;
+    for item in &mut download_items {
+        if cli.priority.iter().any(|glob| util::path_matches_glob(&item.source_name(), glob)) {
+            item.priority = item.priority.max(1);
+        }
+    }
+    // Downloads are admitted in list order, so this is all the scheduler needs; the sort is stable.
+    download_items.sort_by_key(|item| std::cmp::Reverse(item.priority));
+
+    if let Some(command) = &cli.name_command {
+        naming::apply_name_command(&mut download_items, command);
+    }
+
+    let to_stdout = cli.output.as_deref() == Some(Path::new("-"));
+    if to_stdout && cli.strict_verify {
+        // Streamed bytes are gone before they could be hashed.
+        return Err(anyhow::anyhow!("--strict-verify cannot verify a download streamed to stdout (-O -)."));
+    }
+    if to_stdout && cli.checksums_url.is_some() {
+        return Err(anyhow::anyhow!("--checksums-url cannot verify a download streamed to stdout (-O -)."));
+    }
+    if let Some(output) = &cli.output {
+        if download_items.len() > 1 {
+            return Err(anyhow::anyhow!(
+                "-O/--output needs exactly one file to download, but {} were found.",
+                download_items.len()
+            ));
+        }
+        if !to_stdout {
+            if output.is_dir() {
+                return Err(anyhow::anyhow!(
+                    "-O/--output {} is an existing directory; give the path of the file to write.",
+                    output.display()
+                ));
+            }
+            download_dir = match output.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            let file_name = output
+                .file_name()
+                .ok_or_else(|| anyhow::anyhow!("-O/--output must name a file: {}", output.display()))?;
+            download_items[0].preferred_filename = Some(file_name.to_string_lossy().to_string());
+        }
+    }
+
+    // A dry run writes nothing, so it doesn't need (or leave behind) the directory either.
+    if !to_stdout && !cli.dry_run {
+        util::create_dir_checked(&download_dir).await?;
+    }
+
+    let options = DownloadOptions {
+        concurrency,
+        hf_token: hf_token.to_string(),
+        clean: cli.clean,
+        jsonl: cli.jsonl,
+        cas_dir: cli.cas,
+        retries: cli.retries,
+        retry_connect_errors: !cli.no_retry_connect_errors,
+        status_file: cli.status_file,
+        allow_html: cli.allow_html,
+        chunk_size: cli.chunk_size,
+        prescan_concurrency,
+        metered,
+        fail_fast: cli.fail_fast,
+        skip_missing: cli.skip_missing,
+        split: cli.split,
+        min_split_size: cli.min_split_size,
+        force_files: cli.force_files,
+        overwrite_if_smaller: cli.overwrite_if_smaller,
+        timeout_per_file: cli.timeout_per_file.map(std::time::Duration::from_secs),
+        force_prescan: cli.force_prescan,
+        to_stdout,
+        verify_readback: cli.verify_readback,
+        dry_run: cli.dry_run,
+        strict_verify: cli.strict_verify,
+        host_auth: cli.auth,
+        continue_on_disk_full: cli.continue_on_disk_full,
+        compact: cli.compact,
+        exclude_existing_in: cli.exclude_existing_in,
+        link_existing: cli.link_existing,
+        checksum_retries: cli.retry_on_checksum_mismatch,
+        max_total_size: cli.max_total_size,
+        top_files: cli
+            .top_largest
+            .map(TopFiles::Largest)
+            .or(cli.top_smallest.map(TopFiles::Smallest)),
+        client_options,
+    };
+    run_downloads(download_items, download_dir, &options).await?;
+    if let Some((files, dir)) = &repair_check {
+        repair::verify_repaired(files, dir).await?;
+    }
 
     Ok(())
 }
\ No newline at end of file
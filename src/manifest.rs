@@ -0,0 +1,205 @@
+use crate::checksum::is_sha256_hex;
+use crate::downloader::{ChecksumSource, DownloadItem};
+use crate::util::{get_api_client, headers_for_url, ClientOptions, HostAuth};
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// One object of a `--manifest` JSON array.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ManifestEntry {
+    url: String,
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    sha256: Option<String>,
+    #[serde(default)]
+    mirrors: Vec<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    priority: i32,
+}
+
+/// Reads a JSON manifest and turns each validated entry into a download item.
+pub async fn read_manifest(path: &Path) -> Result<Vec<DownloadItem>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+    let entries: Vec<ManifestEntry> = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse manifest {}", path.display()))?;
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            to_download_item(entry)
+                .with_context(|| format!("Invalid entry #{} in manifest {}", i + 1, path.display()))
+        })
+        .collect()
+}
+
+fn to_download_item(entry: ManifestEntry) -> Result<DownloadItem> {
+    validate_url(&entry.url)?;
+    for mirror in &entry.mirrors {
+        validate_url(mirror)?;
+    }
+    let sha256 = match entry.sha256 {
+        Some(sha256) if is_sha256_hex(sha256.trim()) => Some(sha256.trim().to_lowercase()),
+        Some(sha256) => return Err(anyhow!("'{}' is not a hex SHA-256 digest", sha256)),
+        None => None,
+    };
+    let filename = entry.filename.filter(|f| !f.trim().is_empty());
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in entry.headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow!("'{}' is not a valid header name", name))?;
+        let header_value = HeaderValue::from_str(&value)
+            .map_err(|_| anyhow!("Header '{}' has an invalid value", name))?;
+        headers.insert(header_name, header_value);
+    }
+
+    Ok(DownloadItem {
+        url: entry.url,
+        preferred_filename: filename,
+        checksum_source: sha256.is_some().then_some(ChecksumSource::Manifest),
+        sha256,
+        mirrors: entry.mirrors,
+        headers,
+        priority: entry.priority,
+        ..Default::default()
+    })
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    let parsed = url::Url::parse(url).map_err(|e| anyhow!("Invalid URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!("Invalid URL '{}': scheme must be http or https", url));
+    }
+    Ok(())
+}
+
+/// Downloads a `sha256sum`-style checksums file (such as a release's SHA256SUMS) and attaches each
+/// entry's digest to the item with the same relative path, or else the same file name when only one
+/// entry has it. Items that already declare a SHA-256 keep it. Items without an entry, items whose
+/// name matches conflicting entries and entries matching no item are reported.
+pub async fn apply_checksums_url(
+    items: &mut [DownloadItem],
+    url: &str,
+    auth: &[HostAuth],
+    client_options: ClientOptions,
+) -> Result<()> {
+    validate_url(url)?;
+    eprintln!("[INFO] Fetching checksums from {}", url);
+    let client = get_api_client("", client_options)?;
+    let response = client
+        .get(url)
+        .headers(headers_for_url(url, &HeaderMap::new(), auth))
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch checksums file {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Failed to fetch checksums file {}", url))?;
+    let content = response
+        .text()
+        .await
+        .with_context(|| format!("Failed to read checksums file {}", url))?;
+    let entries = parse_checksums(&content).with_context(|| format!("Invalid checksums file {}", url))?;
+
+    // Entry indices by relative path and by base name, so same-named files in different
+    // directories each get their own digest.
+    let mut by_path: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (name, _)) in entries.iter().enumerate() {
+        by_path.entry(normalize_path(name)).or_default().push(i);
+        by_name.entry(base_name(name)).or_default().push(i);
+    }
+    let mut used = HashSet::new();
+    let mut unlisted = Vec::new();
+    let mut ambiguous = Vec::new();
+    for item in items.iter_mut() {
+        let path = normalize_path(&item.source_name());
+        // The full path decides when it is listed; the base name only when no other entry shares it.
+        let candidates = by_path.get(&path).or_else(|| by_name.get(base_name(&path)));
+        let Some(candidates) = candidates else {
+            unlisted.push(path);
+            continue;
+        };
+        let digest = &entries[candidates[0]].1;
+        if candidates.iter().any(|&i| !entries[i].1.eq_ignore_ascii_case(digest)) {
+            ambiguous.push(path);
+            continue;
+        }
+        used.extend(candidates.iter().copied());
+        if item.sha256.is_none() {
+            item.sha256 = Some(digest.to_string());
+            item.checksum_source = Some(ChecksumSource::ChecksumsFile);
+        }
+    }
+    for path in &unlisted {
+        eprintln!("[WARN] {} has no entry in the checksums file and won't be verified.", path);
+    }
+    for path in &ambiguous {
+        eprintln!(
+            "[WARN] {} matches several entries with different digests in the checksums file and won't be verified.",
+            path
+        );
+    }
+    let mut not_downloaded: Vec<&str> = (0..entries.len())
+        .filter(|i| !used.contains(i))
+        .map(|i| entries[i].0.as_str())
+        .collect();
+    if !not_downloaded.is_empty() {
+        not_downloaded.sort_unstable();
+        eprintln!(
+            "[INFO] {} file(s) in the checksums file are not being downloaded: {}",
+            not_downloaded.len(),
+            not_downloaded.join(", ")
+        );
+    }
+    eprintln!(
+        "[INFO] Matched {} of {} download(s) to the checksums file.",
+        items.len() - unlisted.len() - ambiguous.len(),
+        items.len()
+    );
+    Ok(())
+}
+
+/// Parses `sha256sum` output: one `<hex digest>  <name>` per line, where a `*` before the name
+/// marks binary mode. Blank lines and `#` comments are ignored.
+fn parse_checksums(content: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (digest, name) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow!("line {}: expected '<sha256>  <file name>'", i + 1))?;
+        if !is_sha256_hex(digest) {
+            return Err(anyhow!("line {}: '{}' is not a hex SHA-256 digest", i + 1, digest));
+        }
+        let name = name.trim_start_matches([' ', '\t']);
+        let name = name.strip_prefix(['*', ' ']).unwrap_or(name);
+        if name.is_empty() {
+            return Err(anyhow!("line {}: missing file name", i + 1));
+        }
+        entries.push((name.to_string(), digest.to_lowercase()));
+    }
+    Ok(entries)
+}
+
+/// Entries are matched by file name alone, so `./dist/app.tar.gz` matches a download saved as `app.tar.gz`.
+fn base_name(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+/// Compares paths from the checksums file and the download list with `/` separators and no leading `./`.
+fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").to_string()
+}
@@ -0,0 +1,121 @@
+use chrono::Utc;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How metadata requests (repo listings and search) use the on-disk cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// No `--max-age`, or `--no-cache`: always ask the server and neither read nor write the cache.
+    #[default]
+    Disabled,
+    /// Reuse a cached response younger than this instead of asking the server; older ones are
+    /// revalidated with their `ETag`.
+    MaxAge(Duration),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    /// Unix time the response was received (or last confirmed unchanged).
+    fetched_at: i64,
+    body: String,
+    /// The response's `ETag`, sent back as `If-None-Match` to revalidate the entry.
+    #[serde(default)]
+    etag: Option<String>,
+}
+
+/// A cached response, possibly too old to use without asking the server.
+pub struct CachedResponse {
+    pub body: String,
+    pub etag: Option<String>,
+    /// Younger than `--max-age`, so usable without a request.
+    pub fresh: bool,
+}
+
+/// `DL_CACHE_DIR`, else the platform's per-user cache directory.
+fn cache_dir() -> Option<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    if let Some(dir) = env_dir("DL_CACHE_DIR") {
+        return Some(dir);
+    }
+    let base = if cfg!(windows) {
+        env_dir("LOCALAPPDATA")?
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME")?.join("Library").join("Caches")
+    } else {
+        env_dir("XDG_CACHE_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".cache")))?
+    };
+    Some(base.join("dl").join("metadata"))
+}
+
+/// Entries are keyed by the token as well as the URL, so a listing of a gated or private repo
+/// fetched with a token is never served to a run without it (or with another account's).
+fn entry_path(url: &str, token: &str) -> Option<PathBuf> {
+    let fingerprint = if token.is_empty() {
+        "anon".to_string()
+    } else {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    };
+    let key = Sha256::digest(format!("{}\n{}", fingerprint, url).as_bytes());
+    Some(cache_dir()?.join(format!("{:x}.json", key)))
+}
+
+/// The cached body for `url` if the policy allows reusing it and it is young enough.
+pub async fn lookup(url: &str, token: &str, policy: CachePolicy) -> Option<String> {
+    load(url, token, policy).await.filter(|cached| cached.fresh).map(|cached| cached.body)
+}
+
+/// The cached response for `url`, fresh or not, unless the cache is disabled. Stale entries are
+/// still useful for revalidating with their `ETag`.
+pub async fn load(url: &str, token: &str, policy: CachePolicy) -> Option<CachedResponse> {
+    let CachePolicy::MaxAge(max_age) = policy else {
+        return None;
+    };
+    let content = tokio::fs::read_to_string(entry_path(url, token)?).await.ok()?;
+    let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+    if entry.url != url {
+        return None;
+    }
+    let age = Utc::now().timestamp().saturating_sub(entry.fetched_at);
+    let fresh = age >= 0 && age as u64 <= max_age.as_secs();
+    debug!("Cached response for {} is {}s old (fresh: {})", url, age, fresh);
+    Some(CachedResponse {
+        body: entry.body,
+        etag: entry.etag,
+        fresh,
+    })
+}
+
+/// Saves a successful response for later `--max-age` runs. Failures only cost the next run a
+/// request, so they are logged and otherwise ignored.
+pub async fn store(url: &str, token: &str, policy: CachePolicy, body: &str, etag: Option<&str>) {
+    if policy == CachePolicy::Disabled {
+        return;
+    }
+    let Some(path) = entry_path(url, token) else {
+        return;
+    };
+    let entry = CacheEntry {
+        url: url.to_string(),
+        fetched_at: Utc::now().timestamp(),
+        body: body.to_string(),
+        etag: etag.map(str::to_string),
+    };
+    let result = async {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write then rename, so a concurrent reader never sees half an entry.
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&temp, serde_json::to_vec(&entry)?).await?;
+        tokio::fs::rename(&temp, &path).await?;
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        debug!("Could not cache the response for {} in {}: {:#}", url, path.display(), e);
+    }
+}
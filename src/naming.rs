@@ -0,0 +1,87 @@
+use crate::downloader::DownloadItem;
+use crate::progress::print_message;
+use crate::util::sanitize_filename;
+use log::debug;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Asks `--name-command` for each item's local filename. Items keep their default name when the
+/// command fails, prints nothing, or prints a path that isn't a plain relative one.
+pub fn apply_name_command(items: &mut [DownloadItem], command: &str) {
+    let mut renamed = 0;
+    for item in items.iter_mut() {
+        let default_name = item.source_name();
+        match run_name_command(command, &item.url, &default_name) {
+            Ok(Some(name)) => {
+                debug!("--name-command named {} as {}", item.url, name);
+                item.preferred_filename = Some(name);
+                renamed += 1;
+            }
+            Ok(None) => {}
+            Err(reason) => print_message(format!(
+                "[WARN] --name-command for {} {}; keeping {}.",
+                item.url, reason, default_name
+            )),
+        }
+    }
+    eprintln!("[INFO] --name-command named {} of {} file(s).", renamed, items.len());
+}
+
+/// Runs the command through the shell with the URL and the default filename on stdin (one per
+/// line, also in DL_URL and DL_FILENAME). Empty output means "keep the default".
+fn run_name_command(command: &str, url: &str, default_name: &str) -> Result<Option<String>, String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    let mut child = shell
+        .env("DL_URL", url)
+        .env("DL_FILENAME", default_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("could not be started ({})", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that ignores its input may exit before reading it; that's fine.
+        let _ = writeln!(stdin, "{}\n{}", url, default_name);
+    }
+    let output = child.wait_with_output().map_err(|e| format!("failed ({})", e))?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    let stdout = String::from_utf8(output.stdout).map_err(|_| "printed a name that isn't UTF-8".to_string())?;
+    let name = stdout.trim();
+    if name.is_empty() {
+        return Ok(None);
+    }
+    check_name(name)?;
+    // Keep the directories the command asked for, but make each component a valid filename.
+    let components: Vec<String> = name
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .map(sanitize_filename)
+        .collect();
+    Ok((!components.is_empty()).then(|| components.join("/")))
+}
+
+/// Only plain relative paths are accepted, so the command can't write outside the download directory.
+fn check_name(name: &str) -> Result<(), String> {
+    if name.chars().any(char::is_control) {
+        return Err("printed a name with control characters or several lines".to_string());
+    }
+    // Split on both separators: a backslash is an ordinary character on Unix but not on Windows.
+    let escapes = Path::new(name).is_absolute()
+        || name.starts_with(['/', '\\'])
+        || name.split(['/', '\\']).any(|part| part == "..");
+    if escapes {
+        return Err(format!("printed '{}', which is not a relative path inside the download directory", name));
+    }
+    Ok(())
+}
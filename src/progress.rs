@@ -0,0 +1,324 @@
+use crate::util::{format_bytes, format_duration_human};
+use indicatif::{MultiProgress, ProgressBar};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+// How often a progress line is printed when stderr is not a terminal.
+const TEXT_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+// Throughput is sampled at this interval and smoothed with an exponential moving average.
+const THROUGHPUT_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const THROUGHPUT_SMOOTHING: f64 = 0.2;
+
+// How often the overall ETA is written to the debug log.
+const ETA_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+// How often the --compact activity line is redrawn.
+const COMPACT_LINE_INTERVAL: Duration = Duration::from_millis(500);
+
+// The progress display of the running download phase, if any. User-facing messages are
+// printed through it so they land above the bars instead of tearing them.
+static ACTIVE_PROGRESS: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+/// Routes `print_message` through `multi_progress` until the returned guard is dropped.
+pub fn activate_progress(multi_progress: &MultiProgress) -> ActiveProgressGuard {
+    *ACTIVE_PROGRESS.lock().unwrap() = Some(multi_progress.clone());
+    ActiveProgressGuard
+}
+
+/// Detaches the active progress display when dropped.
+pub struct ActiveProgressGuard;
+
+impl Drop for ActiveProgressGuard {
+    fn drop(&mut self) {
+        *ACTIVE_PROGRESS.lock().unwrap() = None;
+    }
+}
+
+/// Prints a user-facing line to stderr, above the progress bars when they are being drawn.
+pub fn print_message(line: impl AsRef<str>) {
+    let line = line.as_ref();
+    let active = ACTIVE_PROGRESS.lock().unwrap();
+    match active.as_ref() {
+        Some(multi_progress) if !multi_progress.is_hidden() => {
+            if multi_progress.println(line).is_err() {
+                eprintln!("{}", line);
+            }
+        }
+        _ => eprintln!("{}", line),
+    }
+}
+
+/// Returns true when progress bars can be drawn, i.e. stderr is an interactive terminal.
+pub fn stderr_is_interactive() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+/// A download that is currently transferring, tracked through its progress bar.
+pub struct ActiveDownload {
+    pub name: String,
+    pub url: String,
+    pub progress_bar: ProgressBar,
+}
+
+struct ThroughputSample {
+    position: u64,
+    at: Instant,
+    smoothed: Option<f64>,
+    /// Network bytes at the previous sample, and the highest single-interval rate seen so far.
+    transferred: u64,
+    peak: Option<f64>,
+}
+
+/// Files whose size is still unknown, and the summed size of all the others.
+struct Sizing {
+    unsized_files: usize,
+    known_total: u64,
+}
+
+/// Run-wide counters shared between the download tasks and the progress reporters.
+pub struct RunTracker {
+    pub overall_pb: ProgressBar,
+    pub total_files: usize,
+    files_done: AtomicUsize,
+    files_failed: AtomicUsize,
+    /// Bytes actually received this run; unlike the overall bar, excludes resumed prefixes and skips.
+    transferred: AtomicU64,
+    active: Mutex<HashMap<usize, ActiveDownload>>,
+    throughput: Mutex<ThroughputSample>,
+    sizing: Mutex<Sizing>,
+}
+
+impl RunTracker {
+    pub fn new(overall_pb: ProgressBar, total_files: usize) -> Self {
+        RunTracker {
+            overall_pb,
+            total_files,
+            files_done: AtomicUsize::new(0),
+            files_failed: AtomicUsize::new(0),
+            transferred: AtomicU64::new(0),
+            active: Mutex::new(HashMap::new()),
+            throughput: Mutex::new(ThroughputSample {
+                position: 0,
+                at: Instant::now(),
+                smoothed: None,
+                transferred: 0,
+                peak: None,
+            }),
+            sizing: Mutex::new(Sizing {
+                unsized_files: 0,
+                known_total: 0,
+            }),
+        }
+    }
+
+    /// Records how many files started without a known size, and the total of those that had one.
+    pub fn set_sizing(&self, unsized_files: usize, known_total: u64) {
+        *self.sizing.lock().unwrap() = Sizing { unsized_files, known_total };
+    }
+
+    /// Adds a size learned from a download response to the overall total. While the overall bar
+    /// has no length, it gets one once the last unknown size is in; returns true when that happens.
+    pub fn add_learned_size(&self, size: u64) -> bool {
+        let mut sizing = self.sizing.lock().unwrap();
+        sizing.unsized_files = sizing.unsized_files.saturating_sub(1);
+        sizing.known_total += size;
+        if self.overall_pb.length().is_some() {
+            self.overall_pb.inc_length(size);
+            false
+        } else if sizing.unsized_files == 0 {
+            self.overall_pb.set_length(sizing.known_total);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Starts the first sampling interval now, so setup time before the downloads isn't averaged in.
+    pub fn restart_sampling(&self) {
+        let mut sample = self.throughput.lock().unwrap();
+        sample.position = self.overall_pb.position();
+        sample.transferred = self.bytes_transferred();
+        sample.at = Instant::now();
+    }
+
+    /// Folds the bytes transferred since the previous sample into the smoothed throughput.
+    pub fn sample_throughput(&self) {
+        let mut sample = self.throughput.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(sample.at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+        let position = self.overall_pb.position();
+        let rate = position.saturating_sub(sample.position) as f64 / elapsed;
+        sample.smoothed = Some(match sample.smoothed {
+            Some(previous) => previous + THROUGHPUT_SMOOTHING * (rate - previous),
+            None => rate,
+        });
+        let transferred = self.bytes_transferred();
+        let network_rate = transferred.saturating_sub(sample.transferred) as f64 / elapsed;
+        sample.peak = Some(sample.peak.map_or(network_rate, |peak| peak.max(network_rate)));
+        sample.transferred = transferred;
+        sample.position = position;
+        sample.at = now;
+    }
+
+    /// Smoothed aggregate throughput in bytes per second, once at least one sample was taken.
+    pub fn smoothed_bytes_per_sec(&self) -> Option<f64> {
+        self.throughput.lock().unwrap().smoothed
+    }
+
+    /// Fastest aggregate throughput over a single sampling interval, in bytes per second.
+    pub fn peak_bytes_per_sec(&self) -> Option<f64> {
+        self.throughput.lock().unwrap().peak
+    }
+
+    /// Estimated time until all known bytes are transferred, based on the smoothed throughput.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.overall_pb.length().filter(|&l| l > 0)?;
+        let remaining = total.saturating_sub(self.overall_pb.position());
+        let rate = self.smoothed_bytes_per_sec().filter(|&r| r > 0.0)?;
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    pub fn record_transferred(&self, bytes: u64) {
+        self.transferred.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn bytes_transferred(&self) -> u64 {
+        self.transferred.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&self, id: usize, download: ActiveDownload) {
+        self.active.lock().unwrap().insert(id, download);
+    }
+
+    pub fn finish(&self, id: usize, failed: bool) {
+        self.active.lock().unwrap().remove(&id);
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.files_failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn files_done(&self) -> usize {
+        self.files_done.load(Ordering::Relaxed)
+    }
+
+    pub fn files_failed(&self) -> usize {
+        self.files_failed.load(Ordering::Relaxed)
+    }
+
+    /// The active download with the lowest current rate among those `include` accepts, as (id, name).
+    pub fn slowest_active(&self, mut include: impl FnMut(usize) -> bool) -> Option<(usize, String)> {
+        self.active
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(&id, _)| include(id))
+            .min_by(|(_, a), (_, b)| a.progress_bar.per_sec().total_cmp(&b.progress_bar.per_sec()))
+            .map(|(&id, download)| (id, download.name.clone()))
+    }
+
+    /// Calls `f` for each active download, in no particular order.
+    pub fn for_each_active(&self, mut f: impl FnMut(&ActiveDownload)) {
+        for download in self.active.lock().unwrap().values() {
+            f(download);
+        }
+    }
+}
+
+/// Samples the aggregate throughput for the ETA and periodically logs the estimate.
+pub fn spawn_eta_monitor(tracker: Arc<RunTracker>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(THROUGHPUT_SAMPLE_INTERVAL);
+        let mut last_logged = Instant::now();
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            tracker.sample_throughput();
+            if last_logged.elapsed() >= ETA_LOG_INTERVAL {
+                last_logged = Instant::now();
+                log::info!("Overall progress: {}", format_progress_line(&tracker));
+            }
+        }
+    })
+}
+
+/// Periodically prints a single-line summary of the overall progress, for piped or redirected stderr.
+pub fn spawn_text_progress(tracker: Arc<RunTracker>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TEXT_PROGRESS_INTERVAL);
+        // The first tick completes immediately; skip it so we don't print 0% right away.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            eprintln!("{}", format_progress_line(&tracker));
+        }
+    })
+}
+
+/// Keeps `line` (a bar drawn under the overall one) showing which downloads are running, in
+/// place of one bar per file for `--compact`.
+pub fn spawn_compact_line(tracker: Arc<RunTracker>, line: ProgressBar) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COMPACT_LINE_INTERVAL);
+        loop {
+            interval.tick().await;
+            line.set_message(format_active_line(&tracker));
+        }
+    })
+}
+
+/// Formats e.g. `downloading 3: a.gguf 45%, b.gguf 12%, c.gguf 88%`; files of unknown size show bytes.
+fn format_active_line(tracker: &RunTracker) -> String {
+    let mut active = Vec::new();
+    tracker.for_each_active(|download| {
+        let pb = &download.progress_bar;
+        let progress = match pb.length().filter(|&l| l > 0) {
+            Some(length) => format!("{}%", pb.position().min(length) * 100 / length),
+            None => format_bytes(pb.position()),
+        };
+        active.push(format!("{} {}", download.name, progress));
+    });
+    if active.is_empty() {
+        return "waiting...".to_string();
+    }
+    active.sort_unstable();
+    format!("downloading {}: {}", active.len(), active.join(", "))
+}
+
+/// Formats e.g. `[45%] 12.30 GB / 27.10 GB @ 80.00 MB/s, 14/20 files done, about 42 min left`.
+pub fn format_progress_line(tracker: &RunTracker) -> String {
+    let overall_pb = &tracker.overall_pb;
+    let position = overall_pb.position();
+    let total = overall_pb.length().unwrap_or(0);
+    let percent = match (position.min(total) * 100).checked_div(total) {
+        Some(p) => format!("{:>3}%", p),
+        None => "  ?%".to_string(),
+    };
+    let total = match overall_pb.length() {
+        Some(total) => format_bytes(total),
+        None => "?".to_string(),
+    };
+    let rate = tracker.smoothed_bytes_per_sec().unwrap_or_else(|| overall_pb.per_sec());
+    let eta = match tracker.eta() {
+        Some(eta) => format!(", about {} left", format_duration_human(eta, false)),
+        None => String::new(),
+    };
+    format!(
+        "[{}] {} / {} @ {}/s, {}/{} files done{}",
+        percent,
+        format_bytes(position),
+        total,
+        format_bytes(rate as u64),
+        tracker.files_done(),
+        tracker.total_files,
+        eta
+    )
+}
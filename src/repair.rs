@@ -0,0 +1,111 @@
+use crate::checksum::sha256_file_async;
+use crate::downloader::gguf_series_key;
+use crate::hf::HFFile;
+use crate::util::{format_bytes, generate_actual_filename};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// What a local copy of a repo file looks like compared to the Hub's metadata.
+enum LocalState {
+    Missing,
+    /// Shorter than the Hub's size; the download resumes it.
+    Partial(u64),
+    Good,
+    Bad(String),
+}
+
+/// Checks the files already in `dir` against the sizes and SHA-256 digests the Hub declares,
+/// deletes the bad ones and returns the files that still need downloading (missing, partial or
+/// deleted). Files without any declared size or hash are downloaded if missing and kept otherwise.
+pub async fn plan_repair(files: Vec<HFFile>, dir: &Path) -> Result<Vec<HFFile>> {
+    warn_incomplete_series(&files);
+    let total = files.len();
+    let mut to_download = Vec::new();
+    for file in files {
+        let path = local_path(&file, dir);
+        match check_local(&file, &path).await? {
+            LocalState::Good => continue,
+            LocalState::Missing => eprintln!("[INFO] Missing: {}", file.filename),
+            LocalState::Partial(size) => {
+                eprintln!("[INFO] Incomplete ({}): {}", format_bytes(size), file.filename)
+            }
+            LocalState::Bad(reason) => {
+                eprintln!("[WARN] Corrupt ({}), re-downloading: {}", reason, file.filename);
+                tokio::fs::remove_file(&path)
+                    .await
+                    .map_err(|e| anyhow!("Failed to remove {}: {}", path.display(), e))?;
+            }
+        }
+        to_download.push(file);
+    }
+    eprintln!(
+        "[INFO] Repair: {} of {} file(s) already intact, {} to download.",
+        total - to_download.len(),
+        total,
+        to_download.len()
+    );
+    Ok(to_download)
+}
+
+/// Re-checks every file after the repair download and fails unless all are present and match.
+pub async fn verify_repaired(files: &[HFFile], dir: &Path) -> Result<()> {
+    let mut problems = Vec::new();
+    for file in files {
+        match check_local(file, &local_path(file, dir)).await? {
+            LocalState::Good => {}
+            LocalState::Missing => problems.push(format!("{} is missing", file.filename)),
+            LocalState::Partial(_) => problems.push(format!("{} is incomplete", file.filename)),
+            LocalState::Bad(reason) => problems.push(format!("{} is corrupt ({})", file.filename, reason)),
+        }
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!("Repair did not succeed: {}", problems.join("; ")));
+    }
+    eprintln!("[INFO] Verified {} file(s): all present and matching the Hub.", files.len());
+    Ok(())
+}
+
+fn local_path(file: &HFFile, dir: &Path) -> PathBuf {
+    dir.join(generate_actual_filename(&file.url, Some(&file.filename)))
+}
+
+async fn check_local(file: &HFFile, path: &Path) -> Result<LocalState> {
+    let size = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(LocalState::Missing),
+        Err(e) => return Err(anyhow!("Failed to inspect {}: {}", path.display(), e)),
+    };
+    if let Some(expected) = file.size {
+        if size < expected {
+            return Ok(LocalState::Partial(size));
+        }
+        if size > expected {
+            return Ok(LocalState::Bad(format!("{} bytes, expected {}", size, expected)));
+        }
+    }
+    if let Some(expected) = &file.sha256 {
+        let actual = sha256_file_async(path).await?;
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Ok(LocalState::Bad("SHA-256 mismatch".to_string()));
+        }
+    }
+    Ok(LocalState::Good)
+}
+
+/// Repair can only fix shards the Hub has; say so when a selected series is missing parts upstream.
+fn warn_incomplete_series(files: &[HFFile]) {
+    let mut series: HashMap<(&str, &str, usize), usize> = HashMap::new();
+    for key in files.iter().filter_map(|file| gguf_series_key(&file.filename)) {
+        *series.entry(key).or_default() += 1;
+    }
+    for ((directory, stem, total_parts), present) in series {
+        if present < total_parts {
+            let name = if directory.is_empty() { stem.to_string() } else { format!("{}/{}", directory, stem) };
+            eprintln!(
+                "[WARN] Series {} has only {} of {} parts on the Hub; the missing parts can't be repaired.",
+                name, present, total_parts
+            );
+        }
+    }
+}
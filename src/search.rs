@@ -1,9 +1,19 @@
-use crate::util::{format_large_number, get_client};
+use crate::hf::fetch_hugging_face_urls;
+use crate::metadata_cache;
+use crate::util::{format_large_number, get_api_client, ClientOptions};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use futures_util::stream::StreamExt;
+use log::{debug, warn};
 use serde::de::{self, Visitor};
 use serde::Deserialize;
 use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Follow-up repo listings for --has-gguf: how many run at once and the minimum gap between starts.
+const GGUF_CHECK_CONCURRENCY: usize = 4;
+const GGUF_CHECK_MIN_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -105,11 +115,17 @@ impl<'de> Deserialize<'de> for GatedStatus {
     }
 }
 
-pub async fn handle_model_search(query: &str, hf_token: &str) -> Result<()> {
+pub async fn handle_model_search(
+    query: &str,
+    hf_token: &str,
+    endpoint: &str,
+    has_gguf: bool,
+    client_options: ClientOptions,
+) -> Result<()> {
     eprintln!("[INFO] Searching for models matching '{}' on Hugging Face...", query);
 
-    let client = get_client(hf_token)?;
-    let api_url = "https://huggingface.co/api/models";
+    let client = get_api_client(hf_token, client_options)?;
+    let api_url = format!("{}/api/models", endpoint);
 
     let params = [
         ("search", query),
@@ -119,32 +135,57 @@ pub async fn handle_model_search(query: &str, hf_token: &str) -> Result<()> {
         ("full", "true"),
     ];
 
-    let resp = client
-        .get(api_url)
-        .query(&params)
-        .send()
-        .await
-        .context("Failed to send search request to Hugging Face API")?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let error_body =
-            resp.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
-        return Err(anyhow::anyhow!(
-            "Hugging Face API request failed with status {}. Response: {}",
-            status,
-            error_body
-        ));
-    }
+    let search_url = reqwest::Url::parse_with_params(&api_url, &params)
+        .with_context(|| format!("Invalid search URL {}", api_url))?;
+    let cache_policy = client_options.metadata_cache;
+    let cached = metadata_cache::lookup(search_url.as_str(), hf_token, cache_policy).await;
+    let from_cache = cached.is_some();
+    let body = match cached {
+        Some(body) => body,
+        None => {
+            let resp = client
+                .get(search_url.clone())
+                .send()
+                .await
+                .context("Failed to send search request to Hugging Face API")?;
+
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let error_body =
+                    resp.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
+                return Err(anyhow::anyhow!(
+                    "Hugging Face API request failed with status {}. Response: {}",
+                    status,
+                    error_body
+                ));
+            }
+            resp.text().await.context("Failed to read search results")?
+        }
+    };
 
     let results: Vec<HFApiModelInfo> =
-        resp.json().await.context("Failed to parse search results JSON")?;
+        serde_json::from_str(&body).context("Failed to parse search results JSON")?;
+    if !from_cache {
+        metadata_cache::store(search_url.as_str(), hf_token, cache_policy, &body, None).await;
+    }
 
     if results.is_empty() {
         eprintln!("[INFO] No models found matching your query '{}'.", query);
         return Ok(());
     }
 
+    let results = if has_gguf {
+        let found = results.len();
+        let filtered = filter_repos_with_gguf(results, hf_token, endpoint, client_options).await;
+        eprintln!("[INFO] {} of {} result(s) contain GGUF files.", filtered.len(), found);
+        if filtered.is_empty() {
+            return Ok(());
+        }
+        filtered
+    } else {
+        results
+    };
+
     println!("\nTop {} model results for \"{}\" (sorted by downloads):", results.len(), query);
     println!("{}", "=".repeat(80));
 
@@ -186,4 +227,48 @@ pub async fn handle_model_search(query: &str, hf_token: &str) -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Keeps only models whose repo lists at least one `.gguf` file, checking each repo with bounded
+/// concurrency and a minimum spacing between requests. Listings go through the metadata cache like
+/// any repo listing, so `--max-age` spares repeated searches the requests. Repos that can't be
+/// listed are dropped.
+async fn filter_repos_with_gguf(
+    models: Vec<HFApiModelInfo>,
+    hf_token: &str,
+    endpoint: &str,
+    client_options: ClientOptions,
+) -> Vec<HFApiModelInfo> {
+    let next_start = Arc::new(Mutex::new(Instant::now()));
+
+    let checks = models.into_iter().map(|model| {
+        let next_start = next_start.clone();
+        async move {
+            let wait = {
+                let mut next = next_start.lock().unwrap();
+                let now = Instant::now();
+                let start = (*next).max(now);
+                *next = start + GGUF_CHECK_MIN_INTERVAL;
+                start - now
+            };
+            tokio::time::sleep(wait).await;
+
+            let has_gguf = match fetch_hugging_face_urls(&model.model_id, hf_token, endpoint, client_options).await {
+                Ok(files) => files.iter().any(|f| f.filename.to_lowercase().ends_with(".gguf")),
+                Err(e) => {
+                    warn!("Could not list files for {}: {:#}", model.model_id, e);
+                    false
+                }
+            };
+            debug!("{} has GGUF files: {}", model.model_id, has_gguf);
+            (model, has_gguf)
+        }
+    });
+
+    // `buffered` keeps the original (download-sorted) order of the results.
+    futures_util::stream::iter(checks)
+        .buffered(GGUF_CHECK_CONCURRENCY)
+        .filter_map(|(model, has_gguf)| async move { has_gguf.then_some(model) })
+        .collect()
+        .await
+}
@@ -0,0 +1,140 @@
+use crate::progress::RunTracker;
+use crate::util::{format_bytes, format_duration_human};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+// How often the status file is rewritten during a run.
+const STATUS_FILE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActiveStatus {
+    pub name: String,
+    pub url: String,
+    pub bytes: u64,
+    pub total: Option<u64>,
+}
+
+/// A point-in-time view of a download run, written by `--status-file`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StatusSnapshot {
+    pub updated_at: DateTime<Utc>,
+    pub finished: bool,
+    pub total_files: usize,
+    pub queued: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub bytes_per_sec: f64,
+    /// Estimated seconds until completion, from the smoothed throughput.
+    pub eta_secs: Option<u64>,
+    pub active: Vec<ActiveStatus>,
+}
+
+impl StatusSnapshot {
+    pub fn capture(tracker: &RunTracker, finished: bool) -> Self {
+        let mut active = Vec::new();
+        tracker.for_each_active(|download| {
+            active.push(ActiveStatus {
+                name: download.name.clone(),
+                url: download.url.clone(),
+                bytes: download.progress_bar.position(),
+                total: download.progress_bar.length().filter(|&l| l > 0),
+            })
+        });
+        active.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Separate relaxed loads: `failed` may already count a file `files_done` doesn't yet.
+        let files_done = tracker.files_done();
+        let failed = tracker.files_failed();
+        StatusSnapshot {
+            updated_at: Utc::now(),
+            finished,
+            total_files: tracker.total_files,
+            queued: tracker.total_files.saturating_sub(files_done + active.len()),
+            completed: files_done.saturating_sub(failed),
+            failed,
+            bytes_done: tracker.overall_pb.position(),
+            bytes_total: tracker.overall_pb.length().unwrap_or(0),
+            bytes_per_sec: tracker
+                .smoothed_bytes_per_sec()
+                .unwrap_or_else(|| tracker.overall_pb.per_sec()),
+            eta_secs: if finished { None } else { tracker.eta().map(|eta| eta.as_secs()) },
+            active,
+        }
+    }
+}
+
+/// Replaces the status file atomically by writing a sibling temp file and renaming it over.
+pub async fn write_status_file(path: &Path, snapshot: &StatusSnapshot) -> Result<()> {
+    let json = serde_json::to_string_pretty(snapshot)?;
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    tokio::fs::write(&tmp_path, json)
+        .await
+        .with_context(|| format!("Failed to write status file: {}", tmp_path.display()))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("Failed to replace status file: {}", path.display()))?;
+    Ok(())
+}
+
+/// Rewrites the status file periodically until aborted.
+pub fn spawn_status_writer(path: PathBuf, tracker: Arc<RunTracker>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATUS_FILE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = write_status_file(&path, &StatusSnapshot::capture(&tracker, false)).await {
+                warn!("{:#}", e);
+            }
+        }
+    })
+}
+
+/// Pretty-prints a status file written by another run (`dl status <file>`).
+pub fn handle_status(path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read status file: {}", path.display()))?;
+    let snapshot: StatusSnapshot =
+        serde_json::from_str(&content).context("Failed to parse status file")?;
+
+    let state = if snapshot.finished { "finished" } else { "running" };
+    println!(
+        "Run {} (updated {})",
+        state,
+        snapshot.updated_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+    );
+    println!(
+        "Files: {} total, {} completed, {} failed, {} active, {} queued",
+        snapshot.total_files,
+        snapshot.completed,
+        snapshot.failed,
+        snapshot.active.len(),
+        snapshot.queued
+    );
+    println!(
+        "Bytes: {} / {} @ {}/s",
+        format_bytes(snapshot.bytes_done),
+        format_bytes(snapshot.bytes_total),
+        format_bytes(snapshot.bytes_per_sec as u64)
+    );
+    if let Some(eta) = snapshot.eta_secs {
+        println!("ETA: about {}", format_duration_human(Duration::from_secs(eta), false));
+    }
+    if !snapshot.active.is_empty() {
+        println!("{}", "-".repeat(40));
+        for download in &snapshot.active {
+            let total = download.total.map(format_bytes).unwrap_or_else(|| "?".to_string());
+            println!("  {} {} / {}", download.name, format_bytes(download.bytes), total);
+        }
+    }
+    Ok(())
+}
@@ -1,13 +1,13 @@
 use crate::config::{CURRENT_APP_VERSION, DEVELOPMENT_VERSION, UPDATER_REPO_NAME, UPDATER_REPO_OWNER};
-use crate::util;
+use crate::util::{self, ClientOptions};
 use anyhow::{anyhow, Context, Result};
 use log::{debug, info};
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::Deserialize;
 use std::env;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Deserialize, Debug)]
 struct GHAsset {
@@ -21,8 +21,18 @@ struct GHRelease {
     tag_name: String,
     name: String,
     assets: Vec<GHAsset>,
+    /// Release notes (markdown); absent or null when the release has none.
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
 }
 
+/// Releases fetched per page of the list endpoint (GitHub's maximum).
+const RELEASES_PER_PAGE: usize = 100;
+
 fn platform_arch_to_asset_name() -> Result<String> {
     let os = env::consts::OS;
     let arch = env::consts::ARCH;
@@ -38,32 +48,201 @@ fn platform_arch_to_asset_name() -> Result<String> {
     Ok(name.to_string())
 }
 
-async fn fetch_latest_release() -> Result<GHRelease> {
+/// Fetches the release with the given tag, or the latest release when `tag` is `None`.
+async fn fetch_release(github_token: &str, tag: Option<&str>, client_options: ClientOptions) -> Result<GHRelease> {
+    let release_path = match tag {
+        Some(tag) => format!("tags/{}", urlencoding::encode(tag)),
+        None => "latest".to_string(),
+    };
     let url = format!(
-        "https://api.github.com/repos/{}/{}/releases/latest",
-        UPDATER_REPO_OWNER, UPDATER_REPO_NAME
+        "https://api.github.com/repos/{}/{}/releases/{}",
+        UPDATER_REPO_OWNER, UPDATER_REPO_NAME, release_path
     );
-    debug!("Fetching latest release from {}", url);
-    let client = util::get_client("")?;
-    let release = client
-        .get(&url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json::<GHRelease>()
-        .await?;
+    debug!("Fetching release from {}", url);
+    let resp = github_get(&url, github_token, client_options).await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        if let Some(tag) = tag {
+            return Err(anyhow!("No release tagged '{}' was found.", tag));
+        }
+    }
+    let release = resp.error_for_status()?.json::<GHRelease>().await?;
     Ok(release)
 }
 
-async fn download_update(url: &str, dest_path: &PathBuf, size: u64) -> Result<()> {
-    let client = util::get_client("")?;
-    let mut resp = client.get(url).send().await?.error_for_status()?;
+/// Sends a GitHub API request, turning an anonymous rate-limit response into a hint about tokens.
+async fn github_get(url: &str, github_token: &str, client_options: ClientOptions) -> Result<reqwest::Response> {
+    if !github_token.is_empty() {
+        debug!("Authenticating GitHub API request with token {}", util::redact_secret(github_token));
+    }
+    let client = util::get_api_client(github_token, client_options)?;
+    let resp = client.get(url).send().await?;
+    let status = resp.status();
+    if (status == reqwest::StatusCode::FORBIDDEN || status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+        && github_token.is_empty()
+    {
+        return Err(anyhow!(
+            "GitHub API request failed with status {} (likely rate-limited). Set GITHUB_TOKEN or pass --github-token.",
+            status
+        ));
+    }
+    Ok(resp)
+}
+
+/// Fetches the published releases newer than `current` and no newer than `latest`, newest first.
+/// Drafts, pre-releases and tags that aren't semver versions are left out.
+async fn fetch_releases_since(
+    github_token: &str,
+    current: &semver::Version,
+    latest: &semver::Version,
+    client_options: ClientOptions,
+) -> Result<Vec<(semver::Version, GHRelease)>> {
+    let mut newer = Vec::new();
+    for page in 1.. {
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/releases?per_page={}&page={}",
+            UPDATER_REPO_OWNER, UPDATER_REPO_NAME, RELEASES_PER_PAGE, page
+        );
+        debug!("Fetching releases from {}", url);
+        let releases = github_get(&url, github_token, client_options)
+            .await?
+            .error_for_status()?
+            .json::<Vec<GHRelease>>()
+            .await?;
+        let last_page = releases.len() < RELEASES_PER_PAGE;
+        // The list is newest first, so a page reaching back to the current version is the last one needed.
+        let mut reached_current = false;
+        for release in releases {
+            if release.draft || release.prerelease {
+                continue;
+            }
+            let Ok(version) = semver::Version::parse(release.tag_name.trim_start_matches('v')) else {
+                debug!("Ignoring release with non-semver tag '{}'", release.tag_name);
+                continue;
+            };
+            if version <= *current {
+                reached_current = true;
+            } else if version <= *latest {
+                newer.push((version, release));
+            }
+        }
+        if last_page || reached_current {
+            break;
+        }
+    }
+    newer.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(newer)
+}
+
+/// Prints the notes of each release, newest first, so skipped versions' changes aren't missed.
+fn print_release_notes(releases: &[&GHRelease]) {
+    eprintln!("[INFO] Changes in {} release(s) since your version:", releases.len());
+    for release in releases {
+        eprintln!();
+        if release.name.is_empty() || release.name == release.tag_name {
+            eprintln!("## {}", release.tag_name);
+        } else {
+            eprintln!("## {} ({})", release.name, release.tag_name);
+        }
+        match release.body.as_deref().map(str::trim) {
+            Some(notes) if !notes.is_empty() => eprintln!("{}", notes),
+            _ => eprintln!("(no release notes)"),
+        }
+    }
+    eprintln!();
+}
+
+/// Asks whether to install the update. Without a terminal to ask on, the update goes ahead as it
+/// would without `--since-release`; the notes are still printed for the log.
+fn confirm_update(tag: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    eprint!("Update to {}? [y/N]: ", tag);
+    std::io::stderr().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// The sidecar holding the `ETag` of the asset a partial update was downloaded from.
+fn etag_path(dest_path: &Path) -> PathBuf {
+    let mut path = dest_path.as_os_str().to_owned();
+    path.push(".etag");
+    PathBuf::from(path)
+}
+
+/// Removes partial updates of other releases of `asset_name` left in `dir`, so they can't pile up.
+fn remove_stale_partials(dir: &Path, asset_name: &str, keep: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let prefix = format!("{}.", asset_name);
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_partial = name.starts_with(&prefix) && (name.ends_with(".new") || name.ends_with(".new.etag"));
+        if is_partial && path != keep && path != etag_path(keep) {
+            debug!("Removing partial update of another release at {}", path.display());
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+async fn download_update(url: &str, dest_path: &PathBuf, size: u64, client_options: ClientOptions) -> Result<()> {
+    let etag_file = etag_path(dest_path);
+    let mut existing = fs::metadata(dest_path).map(|m| m.len()).unwrap_or(0);
+    if existing > size {
+        debug!("Discarding oversized partial update at {}", dest_path.display());
+        fs::remove_file(dest_path)?;
+        existing = 0;
+    }
+    // Resuming is only safe when the server can confirm the asset hasn't changed since.
+    let stored_etag = fs::read_to_string(&etag_file).ok().filter(|etag| !etag.is_empty());
+    if existing > 0 && existing < size && stored_etag.is_none() {
+        debug!("No ETag recorded for the partial update at {}; starting over", dest_path.display());
+        existing = 0;
+    }
+    if size > 0 && existing == size {
+        eprintln!("[INFO] Update was already downloaded, reusing {}", dest_path.display());
+        return Ok(());
+    }
+
+    let client = util::get_client("", client_options)?;
+    let mut request = client.get(url);
+    if existing > 0 {
+        debug!("Resuming update download from byte {}", existing);
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+        // A changed asset is sent whole (200) instead of as a range of the new bytes.
+        if let Some(etag) = &stored_etag {
+            request = request.header(reqwest::header::IF_RANGE, etag.as_str());
+        }
+    }
+    let mut resp = request.send().await?.error_for_status()?;
+
+    let is_resume = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut dest_file = if is_resume {
+        eprintln!("[INFO] Resuming update download at {}.", util::format_bytes(existing));
+        fs::OpenOptions::new().append(true).open(dest_path)?
+    } else {
+        if existing > 0 {
+            eprintln!("[WARN] The update asset changed or the server does not support resume. Starting from beginning.");
+        }
+        existing = 0;
+        let etag = resp.headers().get(reqwest::header::ETAG).and_then(|v| v.to_str().ok());
+        match etag {
+            Some(etag) => fs::write(&etag_file, etag)?,
+            None => {
+                let _ = fs::remove_file(&etag_file);
+            }
+        }
+        fs::File::create(dest_path)?
+    };
     
     let pb = ProgressBar::new(size);
     pb.set_style(ProgressStyle::with_template("{spinner:.green} Downloading update [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?);
 
-    let mut dest_file = fs::File::create(dest_path)?;
-    let mut downloaded: u64 = 0;
+    let mut downloaded: u64 = existing;
+    pb.set_position(downloaded);
 
     while let Some(chunk) = resp.chunk().await? {
         dest_file.write_all(&chunk)?;
@@ -75,26 +254,76 @@ async fn download_update(url: &str, dest_path: &PathBuf, size: u64) -> Result<()
     Ok(())
 }
 
-pub async fn handle_update() -> Result<()> {
+/// Prints every asset of a release with its size and URL, marking the one this platform would install.
+pub async fn handle_list_assets(
+    github_token: &str,
+    tag: Option<&str>,
+    client_options: ClientOptions,
+) -> Result<()> {
+    let release = fetch_release(github_token, tag, client_options)
+        .await
+        .context("Could not fetch release information")?;
+    let target_asset_name = platform_arch_to_asset_name();
+
+    eprintln!("Release {} ({}), {} asset(s):", release.tag_name, release.name, release.assets.len());
+    for asset in &release.assets {
+        let marker = match &target_asset_name {
+            Ok(name) if *name == asset.name => "*",
+            _ => " ",
+        };
+        println!(
+            "{} {:<24} {:>10}  {}",
+            marker,
+            asset.name,
+            util::format_bytes(asset.size),
+            asset.browser_download_url
+        );
+    }
+
+    match target_asset_name {
+        Ok(name) if release.assets.iter().any(|a| a.name == name) => {
+            eprintln!("[INFO] This platform uses '{}' (marked with *).", name);
+        }
+        Ok(name) => {
+            eprintln!("[WARN] This platform expects an asset named '{}', which this release doesn't have.", name);
+        }
+        Err(e) => eprintln!("[WARN] {}", e),
+    }
+    Ok(())
+}
+
+/// Installs the latest release. With `since_release`, first shows the notes of every release
+/// newer than the running version and asks for confirmation.
+pub async fn handle_update(github_token: &str, since_release: bool, client_options: ClientOptions) -> Result<()> {
     info!("Starting self-update process.");
     eprintln!("[INFO] Checking for updates...");
 
     let target_asset_name = platform_arch_to_asset_name()?;
     debug!("Target asset for this platform: {}", target_asset_name);
 
-    let release = fetch_latest_release().await.context("Could not fetch update information")?;
+    let release = fetch_release(github_token, None, client_options).await.context("Could not fetch update information")?;
     info!("Latest release is '{}' with tag '{}'", release.name, release.tag_name);
     
     let current_version = if CURRENT_APP_VERSION == "0.0.0" { DEVELOPMENT_VERSION } else { CURRENT_APP_VERSION };
     
     let should_update = if current_version == DEVELOPMENT_VERSION {
         eprintln!("[INFO] Running a development build. The latest release is {}.", release.tag_name);
+        if since_release {
+            // There's no release to count from, so only the latest one's notes apply.
+            print_release_notes(&[&release]);
+        }
         true
     } else {
         let current_v = semver::Version::parse(current_version.trim_start_matches('v'))?;
         let latest_v = semver::Version::parse(release.tag_name.trim_start_matches('v'))?;
         if latest_v > current_v {
             eprintln!("[INFO] A new version {} is available (current: {}).", latest_v, current_v);
+            if since_release {
+                let releases = fetch_releases_since(github_token, &current_v, &latest_v, client_options)
+                    .await
+                    .context("Could not fetch release notes")?;
+                print_release_notes(&releases.iter().map(|(_, release)| release).collect::<Vec<_>>());
+            }
             true
         } else {
             eprintln!("[INFO] Your version ({}) is up to date.", current_v);
@@ -105,6 +334,10 @@ pub async fn handle_update() -> Result<()> {
     if !should_update {
         return Ok(());
     }
+    if since_release && !confirm_update(&release.tag_name)? {
+        eprintln!("[INFO] Update cancelled.");
+        return Ok(());
+    }
 
     if let Some(asset) = release.assets.iter().find(|a| a.name == target_asset_name) {
         eprintln!(
@@ -114,9 +347,20 @@ pub async fn handle_update() -> Result<()> {
         
         let current_exe = env::current_exe()?;
         let update_dir = current_exe.parent().unwrap();
-        let temp_path = update_dir.join(format!("{}.new", asset.name));
+        // The tag keeps a partial download of one release from being resumed into another.
+        let temp_path = update_dir.join(format!("{}.{}.new", asset.name, util::sanitize_filename(&release.tag_name)));
+        remove_stale_partials(update_dir, &asset.name, &temp_path);
         
-        download_update(&asset.browser_download_url, &temp_path, asset.size).await?;
+        download_update(&asset.browser_download_url, &temp_path, asset.size, client_options).await?;
+
+        let downloaded_size = fs::metadata(&temp_path)?.len();
+        if downloaded_size != asset.size {
+            return Err(anyhow!(
+                "Downloaded update is {} bytes but the release asset is {} bytes; run `dl update` again to resume.",
+                downloaded_size,
+                asset.size
+            ));
+        }
         
         // On unix, set executable permissions
         #[cfg(unix)]
@@ -129,6 +373,7 @@ pub async fn handle_update() -> Result<()> {
         eprintln!("[INFO] Applying update...");
         self_replace::self_replace(&temp_path).map_err(|e| anyhow!("Failed to apply update: {}", e))?;
         fs::remove_file(&temp_path)?;
+        let _ = fs::remove_file(etag_path(&temp_path));
         
         eprintln!("[SUCCESS] Update applied! Please restart the application.");
         Ok(())
@@ -1,6 +1,12 @@
+use crate::cli::{IpPreference, MinTls};
+use crate::metadata_cache::CachePolicy;
+use crate::progress::print_message;
 use anyhow::Result;
+use base64::prelude::{Engine, BASE64_STANDARD};
 use path_clean::PathClean;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use std::backtrace::Backtrace;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::panic::PanicHookInfo;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -53,7 +59,7 @@ pub fn format_duration_human(duration: Duration, show_seconds: bool) -> String {
             }
             return format!("{:.0} hr {:.0} min 0 sec", hours, minutes + 1.0);
         }
-        return format!("{:.0} hr {:.0} min {:.0} sec", hours, minutes, seconds);
+        format!("{:.0} hr {:.0} min {:.0} sec", hours, minutes, seconds)
     } else {
         if total_seconds < 60.0 {
             return "<1 min".to_string();
@@ -67,7 +73,7 @@ pub fn format_duration_human(duration: Duration, show_seconds: bool) -> String {
         if minutes == 60.0 {
             return format!("{:.0} hr 0 min", hours + 1.0);
         }
-        return format!("{:.0} hr {:.0} min", hours, minutes);
+        format!("{:.0} hr {:.0} min", hours, minutes)
     }
 }
 
@@ -86,6 +92,81 @@ pub fn format_large_number(n: u64) -> String {
     format!("{:.1}B", n as f64 / 1_000_000_000.0)
 }
 
+/// Parses a human-friendly size such as `512`, `256KiB`, `64M` or `1.5GB`.
+/// Suffixes without `B` and binary suffixes (`KiB`, `MiB`, ...) are powers of 1024;
+/// decimal suffixes (`KB`, `MB`, ...) are powers of 1000.
+pub fn parse_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let split_at = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split_at);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size '{}': expected a number with an optional unit", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        other => return Err(anyhow::anyhow!("Invalid size '{}': unknown unit '{}'", input, other)),
+    };
+    Ok((value * multiplier as f64).round() as u64)
+}
+
+/// Creates `dir` and any missing parents. When the path or one of its parents exists as a file,
+/// fails with a message saying so instead of the OS's "File exists" or "Not a directory".
+pub async fn create_dir_checked(dir: &Path) -> Result<()> {
+    for ancestor in dir.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+        match tokio::fs::metadata(ancestor).await {
+            Ok(metadata) if metadata.is_dir() => break,
+            Ok(_) if ancestor == dir => {
+                return Err(anyhow::anyhow!(
+                    "Output path '{}' exists and is not a directory.",
+                    dir.display()
+                ));
+            }
+            Ok(_) => {
+                return Err(anyhow::anyhow!(
+                    "Cannot create directory '{}': '{}' exists and is not a directory.",
+                    dir.display(),
+                    ancestor.display()
+                ));
+            }
+            Err(_) => continue,
+        }
+    }
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to create directory {}: {}", dir.display(), e))
+}
+
+/// Parses a duration such as "90", "30s", "10m", "1h" or "2d"; a bare number is seconds.
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let trimmed = input.trim();
+    let split_at = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split_at);
+    let value: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration '{}': expected a number with an optional unit", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        other => return Err(anyhow::anyhow!("Invalid duration '{}': unknown unit '{}' (use s, m, h or d)", input, other)),
+    };
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow::anyhow!("Invalid duration '{}': too large", input))
+}
+
 /// Generates a safe and predictable local filename from a URL and an optional preferred name.
 pub fn generate_actual_filename(url_str: &str, preferred_name: Option<&str>) -> String {
     let file_name = if let Some(name) = preferred_name {
@@ -94,11 +175,11 @@ pub fn generate_actual_filename(url_str: &str, preferred_name: Option<&str>) ->
             || clean_name.starts_with("..")
             || clean_name.components().any(|c| c == std::path::Component::ParentDir)
         {
-            eprintln!(
+            print_message(format!(
                 "[WARN] Preferred name '{}' (cleaned to '{}') attempts path traversal or is absolute. Using only its base name.",
                 name,
                 clean_name.display()
-            );
+            ));
             clean_name
                 .file_name()
                 .unwrap_or_default()
@@ -143,12 +224,12 @@ pub fn generate_actual_filename(url_str: &str, preferred_name: Option<&str>) ->
         } else {
             fallback_name + ".file"
         };
-        eprintln!(
+        print_message(format!(
             "[WARN] Could not determine a valid filename for URL '{}' (preferred: {:?}). Using fallback: {}",
             url_str,
             preferred_name,
             final_name
-        );
+        ));
         final_name
     } else {
         file_name
@@ -183,8 +264,59 @@ pub fn log_panic(info: &PanicHookInfo<'_>) {
 }
 
 
-/// Creates a reqwest client with a default user agent and optional auth token.
-pub fn get_client(hf_token: &str) -> Result<reqwest::Client> {
+/// Connection settings shared by every HTTP client, taken from the global command-line flags.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientOptions {
+    /// Refuse TLS versions below this one.
+    pub min_tls: Option<MinTls>,
+    /// Address family to try first on dual-stack hosts.
+    pub ip_preference: Option<IpPreference>,
+    /// Overall deadline for metadata requests made through `get_api_client`.
+    pub api_timeout: Option<Duration>,
+    /// Whether repo listings and search results may come from the on-disk cache.
+    pub metadata_cache: CachePolicy,
+}
+
+/// Resolves host names with the system resolver, then orders the preferred address family first.
+/// The connector tries the first family and only falls back to the other one shortly after,
+/// so a broken IPv6 (or IPv4) path no longer stalls every connection.
+struct PreferringResolver {
+    preference: IpPreference,
+}
+
+impl reqwest::dns::Resolve for PreferringResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let prefer_v4 = self.preference == IpPreference::V4;
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let mut addrs: Vec<SocketAddr> =
+                tokio::task::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs())
+                    .await??
+                    .collect();
+            // Stable sort keeps the resolver's order within each family.
+            addrs.sort_by_key(|addr| addr.is_ipv4() != prefer_v4);
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Creates a reqwest client with a default user agent, optional auth token and the given connection settings.
+pub fn get_client(hf_token: &str, client_options: ClientOptions) -> Result<reqwest::Client> {
+    Ok(client_builder(hf_token, client_options)?.build()?)
+}
+
+/// Like `get_client`, but for small metadata requests (repo listings, search, release info):
+/// each request fails after `--api-timeout` instead of waiting as long as a bulk download may.
+pub fn get_api_client(token: &str, client_options: ClientOptions) -> Result<reqwest::Client> {
+    let mut builder = client_builder(token, client_options)?;
+    if let Some(timeout) = client_options.api_timeout {
+        builder = builder.timeout(timeout);
+    }
+    Ok(builder.build()?)
+}
+
+fn client_builder(hf_token: &str, client_options: ClientOptions) -> Result<reqwest::ClientBuilder> {
     let mut headers = reqwest::header::HeaderMap::new();
     headers.insert(
         reqwest::header::USER_AGENT,
@@ -197,10 +329,27 @@ pub fn get_client(hf_token: &str) -> Result<reqwest::Client> {
         );
     }
 
-    Ok(reqwest::Client::builder()
+    let mut builder = reqwest::Client::builder()
         .default_headers(headers)
-        .connect_timeout(std::time::Duration::from_secs(20))
-        .build()?)
+        .connect_timeout(std::time::Duration::from_secs(20));
+    if let Some(min_tls) = client_options.min_tls {
+        builder = builder.min_tls_version(match min_tls {
+            MinTls::Tls12 => reqwest::tls::Version::TLS_1_2,
+            MinTls::Tls13 => reqwest::tls::Version::TLS_1_3,
+        });
+    }
+    if let Some(preference) = client_options.ip_preference {
+        builder = builder.dns_resolver(std::sync::Arc::new(PreferringResolver { preference }));
+    }
+    Ok(builder)
+}
+
+/// Masks a secret for logging, keeping only a short prefix so it can still be told apart.
+pub fn redact_secret(secret: &str) -> String {
+    if secret.chars().count() <= 8 {
+        return "***".to_string();
+    }
+    format!("{}***", secret.chars().take(4).collect::<String>())
 }
 
 /// Shortens an error message to a maximum length.
@@ -217,11 +366,31 @@ pub fn shorten_error(err: &anyhow::Error, max_len: usize) -> String {
     }
 }
 
-/// Cleans a repository ID string to be used as a directory name.
-pub fn repo_id_to_safe_path(repo_id: &str) -> String {
-    let cleaned_repo_input = repo_id
+/// Strips a Hugging Face host prefix (the default one or the configured endpoint) from a repo ID.
+pub fn clean_repo_id<'a>(repo_id: &'a str, endpoint: &str) -> &'a str {
+    repo_id
+        .trim_start_matches(&format!("{}/", endpoint))
         .trim_start_matches("https://huggingface.co/")
-        .trim_start_matches("http://huggingface.co/");
+        .trim_start_matches("http://huggingface.co/")
+}
+
+/// Validates a Hugging Face endpoint URL and strips any trailing slash.
+pub fn normalize_endpoint(endpoint: &str) -> Result<String> {
+    let trimmed = endpoint.trim().trim_end_matches('/');
+    let parsed = url::Url::parse(trimmed)
+        .map_err(|e| anyhow::anyhow!("Invalid Hugging Face endpoint '{}': {}", endpoint, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow::anyhow!(
+            "Invalid Hugging Face endpoint '{}': scheme must be http or https",
+            endpoint
+        ));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Cleans a repository ID string to be used as a directory name.
+pub fn repo_id_to_safe_path(repo_id: &str, endpoint: &str) -> String {
+    let cleaned_repo_input = clean_repo_id(repo_id, endpoint);
     
     let parts: Vec<&str> = cleaned_repo_input.split('/').collect();
     if parts.len() >= 2 {
@@ -236,4 +405,271 @@ pub fn repo_id_to_safe_path(repo_id: &str) -> String {
 /// Removes characters that are problematic in filenames.
 pub fn sanitize_filename(name: &str) -> String {
     name.replace(&['/', '\\', ':', '*', '?', '"', '<', '>', '|'][..], "_")
-}
\ No newline at end of file
+}
+
+/// Matches `text` against a glob pattern supporting `*` (any run of characters) and `?` (one character).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text index it is currently matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Compares strings in "natural" order, so version numbers sort as numbers: runs of digits are
+/// compared by value (`v1.10` > `v1.9`), other text case-insensitively. A pre-release suffix such
+/// as `-rc1` or `-beta` sorts before whatever takes its place in the other string, so `v1.3` >
+/// `v1.3-rc1`. Ties fall back to a plain comparison, so the order is total.
+pub fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    fn runs(s: &str) -> Vec<&str> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut in_digits = None;
+        for (i, c) in s.char_indices() {
+            let digit = c.is_ascii_digit();
+            if in_digits.is_some_and(|d| d != digit) {
+                runs.push(&s[start..i]);
+                start = i;
+            }
+            in_digits = Some(digit);
+        }
+        if start < s.len() {
+            runs.push(&s[start..]);
+        }
+        runs
+    }
+    fn is_pre_release(run: &str) -> bool {
+        let run = run.to_lowercase();
+        let Some(marker) = run.strip_prefix(['-', '.', '_']) else {
+            return false;
+        };
+        ["rc", "alpha", "beta", "pre", "dev"].iter().any(|tag| marker.starts_with(tag))
+    }
+    let (runs_a, runs_b) = (runs(a), runs(b));
+    for (x, y) in runs_a.iter().zip(&runs_b) {
+        match (is_pre_release(x), is_pre_release(y)) {
+            (true, false) => return std::cmp::Ordering::Less,
+            (false, true) => return std::cmp::Ordering::Greater,
+            _ => {}
+        }
+        let both_numeric = x.starts_with(|c: char| c.is_ascii_digit()) && y.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = if both_numeric {
+            let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+            x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+        } else {
+            x.to_lowercase().cmp(&y.to_lowercase())
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    // One string is a prefix of the other: a pre-release suffix makes the longer one older.
+    let common = runs_a.len().min(runs_b.len());
+    match (runs_a.get(common), runs_b.get(common)) {
+        (Some(x), None) if is_pre_release(x) => std::cmp::Ordering::Less,
+        (None, Some(y)) if is_pre_release(y) => std::cmp::Ordering::Greater,
+        _ => runs_a.len().cmp(&runs_b.len()).then_with(|| a.cmp(b)),
+    }
+}
+
+/// Matches a relative path against a glob. Patterns without a `/` match the path's base name,
+/// so `tokenizer*` also matches `sub/tokenizer.json`; patterns with a `/` match the full path.
+pub fn path_matches_glob(path: &str, pattern: &str) -> bool {
+    let path = path.replace('\\', "/");
+    if pattern.contains('/') {
+        glob_match(pattern, &path)
+    } else {
+        let base_name = path.rsplit('/').next().unwrap_or(&path);
+        glob_match(pattern, base_name)
+    }
+}
+
+/// Credentials for one host, given as `--auth HOST=USER:PASSWORD` or `--auth HOST=bearer:TOKEN`.
+#[derive(Debug, Clone)]
+pub struct HostAuth {
+    /// Host name or glob, matched like `--allow-host` entries.
+    pub host: String,
+    pub authorization: HeaderValue,
+}
+
+/// Parses an `--auth` value into a host pattern and a ready-made Authorization header.
+pub fn parse_host_auth(value: &str) -> Result<HostAuth, String> {
+    let (host, credentials) = value
+        .split_once('=')
+        .ok_or_else(|| "expected HOST=USER:PASSWORD or HOST=bearer:TOKEN".to_string())?;
+    let host = host.trim();
+    if host.is_empty() || host.contains('/') {
+        return Err(format!("'{}' is not a host name", host));
+    }
+    let header = match credentials.strip_prefix("bearer:") {
+        Some(token) if !token.is_empty() => format!("Bearer {}", token),
+        Some(_) => return Err("the bearer token is empty".to_string()),
+        None => {
+            if !credentials.contains(':') {
+                return Err("credentials must be USER:PASSWORD or bearer:TOKEN".to_string());
+            }
+            format!("Basic {}", BASE64_STANDARD.encode(credentials))
+        }
+    };
+    let mut authorization =
+        HeaderValue::from_str(&header).map_err(|_| "credentials contain invalid characters".to_string())?;
+    authorization.set_sensitive(true);
+    Ok(HostAuth { host: host.to_string(), authorization })
+}
+
+/// The headers to send with a request for `url`: `headers`, plus the first matching `--auth`
+/// credentials unless `headers` already carries an Authorization header. Credentials are only
+/// ever attached to requests for their own host.
+pub fn headers_for_url(url: &str, headers: &HeaderMap, auth: &[HostAuth]) -> HeaderMap {
+    let mut headers = headers.clone();
+    if !headers.contains_key(AUTHORIZATION) {
+        if let Some(entry) = auth.iter().find(|a| host_permitted(url, std::slice::from_ref(&a.host), &[])) {
+            headers.insert(AUTHORIZATION, entry.authorization.clone());
+        }
+    }
+    headers
+}
+
+/// Whether two URLs point at the same host and port (host names compared case-insensitively).
+pub fn same_host(a: &str, b: &str) -> bool {
+    let origin = |url: &str| {
+        let url = url::Url::parse(url).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        Some((host, url.port_or_known_default()))
+    };
+    matches!((origin(a), origin(b)), (Some(a), Some(b)) if a == b)
+}
+
+/// Whether the host of `url` passes the `--allow-host`/`--deny-host` lists. Entries are
+/// case-insensitive host names or globs such as `*.example.com`. Deny wins over allow, an empty
+/// allow list allows every host, and URLs without a host only pass when no allow list is set.
+pub fn host_permitted(url: &str, allow: &[String], deny: &[String]) -> bool {
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.trim_matches(['[', ']']).to_ascii_lowercase()));
+    let matches = |patterns: &[String]| match &host {
+        Some(host) => patterns.iter().any(|p| glob_match(&p.to_ascii_lowercase(), host)),
+        None => false,
+    };
+    if matches(deny) {
+        return false;
+    }
+    allow.is_empty() || matches(allow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn same_host_compares_host_and_port() {
+        assert!(same_host("https://Example.com/a", "https://example.com:443/b"));
+        assert!(!same_host("https://example.com/a", "http://example.com/a"));
+        assert!(!same_host("https://example.com/a", "https://mirror.example.com/a"));
+        assert!(!same_host("not a url", "not a url"));
+    }
+
+    #[test]
+    fn glob_match_supports_star_and_question_mark() {
+        assert!(glob_match("*.gguf", "model-Q4_K_M.gguf"));
+        assert!(glob_match("model-?.bin", "model-1.bin"));
+        assert!(!glob_match("model-?.bin", "model-10.bin"));
+        assert!(glob_match("*Q4*", "model-Q4_K_M.gguf"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*.gguf", "model.gguf.part"));
+        assert!(!glob_match("model", "model.gguf"));
+    }
+
+    #[test]
+    fn path_matches_glob_uses_the_base_name_without_a_slash() {
+        assert!(path_matches_glob("sub/tokenizer.json", "tokenizer*"));
+        assert!(path_matches_glob("tokenizer.json", "tokenizer*"));
+        assert!(path_matches_glob("a\\b\\config.json", "config.json"));
+        assert!(!path_matches_glob("tokenizer/model.bin", "tokenizer*"));
+    }
+
+    #[test]
+    fn path_matches_glob_anchors_patterns_with_a_slash_to_the_full_path() {
+        assert!(path_matches_glob("Q4_K_M/model-00001-of-00002.gguf", "Q4_K_M/*.gguf"));
+        assert!(!path_matches_glob("other/Q4_K_M/model.gguf", "Q4_K_M/*.gguf"));
+        assert!(path_matches_glob("other/Q4_K_M/model.gguf", "*/Q4_K_M/*.gguf"));
+        assert!(path_matches_glob("Q4_K_M\\model.gguf", "Q4_K_M/model.gguf"));
+        assert!(!path_matches_glob("Q8_0/model.gguf", "Q4_K_M/*"));
+    }
+
+    #[test]
+    fn natural_cmp_orders_versions_numerically() {
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("v2", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("model-v1.10.gguf", "model-v1.9.gguf"), Ordering::Greater);
+        assert_eq!(natural_cmp("v1.9", "v1.9"), Ordering::Equal);
+        assert_eq!(natural_cmp("Model-V1", "model-v1"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_cmp_puts_pre_releases_first() {
+        assert_eq!(natural_cmp("v1.3", "v1.3-rc1"), Ordering::Greater);
+        assert_eq!(natural_cmp("v1.3-rc1", "v1.3"), Ordering::Less);
+        assert_eq!(natural_cmp("model-v1.3.gguf", "model-v1.3-rc1.gguf"), Ordering::Greater);
+        assert_eq!(natural_cmp("v1.3-rc2", "v1.3-rc1"), Ordering::Greater);
+        assert_eq!(natural_cmp("v1.3-rc1", "v1.2"), Ordering::Greater);
+    }
+
+    /// A fresh, empty directory under the system temp dir for one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dl-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn create_dir_checked_creates_nested_directories() {
+        let root = temp_dir("create-dir");
+        let dir = root.join("a").join("b");
+        create_dir_checked(&dir).await.unwrap();
+        assert!(dir.is_dir());
+        // Existing directories are fine too.
+        create_dir_checked(&dir).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_dir_checked_rejects_an_existing_file() {
+        let root = temp_dir("create-dir-file");
+        let file = root.join("downloads");
+        std::fs::write(&file, b"x").unwrap();
+        let err = create_dir_checked(&file).await.unwrap_err().to_string();
+        assert!(err.contains("exists and is not a directory"), "{}", err);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_dir_checked_names_a_file_ancestor() {
+        let root = temp_dir("create-dir-parent");
+        let file = root.join("downloads");
+        std::fs::write(&file, b"x").unwrap();
+        let err = create_dir_checked(&file.join("repo")).await.unwrap_err().to_string();
+        assert!(err.contains(&format!("'{}' exists and is not a directory", file.display())), "{}", err);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}